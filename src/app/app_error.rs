@@ -1,31 +1,66 @@
-use hyper::StatusCode;
+use hyper::{Method, StatusCode};
 use serde::Serialize;
 
+use crate::model::ErrorCode;
+use crate::service::ServiceError;
+
 #[derive(Debug, Serialize)]
 pub struct AppError {
     #[serde(skip_serializing)]
-    pub code: StatusCode,
+    pub status: StatusCode,
+    pub code: ErrorCode,
     pub message: Option<String>,
+    /// Params the error is about.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<&'static str>,
+    /// Methods sent in the `Allow` header of a 405.
+    #[serde(skip_serializing)]
+    pub allow: Vec<Method>,
+    /// Seconds sent in the `Retry-After` header of a 503.
+    #[serde(skip_serializing)]
+    pub retry_after: Option<u64>,
 }
 
 impl AppError {
-    pub fn bad_request(message: String) -> AppError {
+    pub fn new(status: StatusCode, code: ErrorCode, message: String) -> AppError {
         AppError {
-            code: StatusCode::BAD_REQUEST,
+            status,
+            code,
             message: Some(message),
+            fields: Vec::new(),
+            allow: Vec::new(),
+            retry_after: None,
         }
     }
 
+    pub fn bad_request(message: String) -> AppError {
+        AppError::new(StatusCode::BAD_REQUEST, ErrorCode::BadRequest, message)
+    }
+
     pub fn secret() -> AppError {
-        AppError {
-            code: StatusCode::UNAUTHORIZED,
-            message: Some("Secret does not match".to_string()),
-        }
+        ServiceError::SecretNotMatch.into()
+    }
+
+    pub fn unauthorized() -> AppError {
+        AppError::new(
+            StatusCode::UNAUTHORIZED,
+            ErrorCode::Unauthorized,
+            "Admin key required".to_string(),
+        )
     }
+
     pub fn not_found(message: String) -> AppError {
+        AppError::new(StatusCode::NOT_FOUND, ErrorCode::NotFound, message)
+    }
+
+    pub fn method_not_allowed(allow: Vec<Method>) -> AppError {
         AppError {
-            code: StatusCode::NOT_FOUND,
-            message: Some(message),
+            allow,
+            ..AppError::new(
+                StatusCode::METHOD_NOT_ALLOWED,
+                ErrorCode::MethodNotAllowed,
+                "Method not allowed".to_string(),
+            )
         }
     }
 }
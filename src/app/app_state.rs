@@ -0,0 +1,128 @@
+use hyper::{Request, StatusCode};
+
+use crate::app::app_error::AppError;
+use crate::app::request_params::bearer;
+use crate::misc::is_same_token;
+use crate::model::ErrorCode;
+use crate::service::{ChatRoom, ChatService};
+
+/// What the handlers share.
+pub struct AppState {
+    pub service: ChatService,
+    /// Bearer token of the server-wide endpoints, they are disabled without one.
+    pub admin_key: Option<String>,
+    /// `/status` and `/debug` don't need the admin key.
+    pub public_status: bool,
+}
+
+impl AppState {
+    pub fn is_admin_key(&self, key: &str) -> bool {
+        self.admin_key
+            .as_deref()
+            .is_some_and(|admin_key| is_same_token(admin_key, key))
+    }
+
+    /// The admin key, or the admin secret of a namespace of `uid`.
+    pub fn is_admin_secret(&self, uid: &str, secret: &str) -> bool {
+        self.is_admin_key(secret) || self.service.namespaces().is_admin_secret(uid, secret)
+    }
+
+    /// Admin secrets can do whatever the secret of a room can.
+    pub fn is_room_secret(&self, uid: &str, room: &ChatRoom, secret: &str) -> bool {
+        room.is_secret(secret) || self.is_admin_secret(uid, secret)
+    }
+
+    /// Checks the admin key of a request, the admin endpoints are disabled until a key is
+    /// configured.
+    pub fn authorize_admin<B>(&self, req: &Request<B>) -> Result<(), AppError> {
+        match &self.admin_key {
+            None => Err(AppError::new(
+                StatusCode::FORBIDDEN,
+                ErrorCode::Forbidden,
+                "Admin endpoints are disabled".to_string(),
+            )),
+            Some(_) if !bearer(req).is_some_and(|e| self.is_admin_key(&e)) => {
+                Err(AppError::unauthorized())
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Like [Self::authorize_admin] for the rooms under `prefix`, which the admin secret of a
+    /// namespace covering it is also enough for.
+    pub fn authorize_prefix<B>(&self, req: &Request<B>, prefix: &str) -> Result<(), AppError> {
+        match bearer(req) {
+            Some(secret) if self.service.namespaces().is_admin_secret(prefix, &secret) => Ok(()),
+            _ => self.authorize_admin(req),
+        }
+    }
+
+    /// Like [Self::authorize_admin] unless `/status` and `/debug` were made public.
+    pub fn authorize_status<B>(&self, req: &Request<B>) -> Result<(), AppError> {
+        if self.public_status {
+            Ok(())
+        } else {
+            self.authorize_admin(req)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::header::AUTHORIZATION;
+
+    use super::*;
+    use crate::model::{Namespace, Namespaces};
+
+    fn request(token: &str) -> Request<()> {
+        Request::builder()
+            .header(AUTHORIZATION, format!("Bearer {token}"))
+            .body(())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_authorize_admin() {
+        let mut state = AppState {
+            service: ChatService::create(None, Namespaces::default()),
+            admin_key: None,
+            public_status: false,
+        };
+        let error = state.authorize_admin(&request("key")).unwrap_err();
+        assert_eq!(error.status, StatusCode::FORBIDDEN);
+        let error = state.authorize_status(&Request::new(())).unwrap_err();
+        assert_eq!(error.status, StatusCode::FORBIDDEN);
+        state.public_status = true;
+        assert!(state.authorize_status(&Request::new(())).is_ok());
+        assert!(state.authorize_admin(&Request::new(())).is_err());
+
+        state.admin_key = Some("key".to_string());
+        assert!(state.authorize_admin(&request("key")).is_ok());
+        let error = state.authorize_admin(&request("other")).unwrap_err();
+        assert_eq!(error.status, StatusCode::UNAUTHORIZED);
+        assert!(state.authorize_admin(&Request::new(())).is_err());
+        assert!(state.is_admin_key("key"));
+        assert!(!state.is_admin_key(""));
+    }
+
+    #[tokio::test]
+    async fn test_namespace_admin() {
+        let dev = Namespace {
+            prefix: "/dev".to_string(),
+            admin_secret: Some("dev".to_string()),
+            ..Default::default()
+        };
+        let state = AppState {
+            service: ChatService::create(None, Namespaces::new(vec![dev]).unwrap()),
+            admin_key: Some("key".to_string()),
+            public_status: false,
+        };
+        assert!(state.authorize_prefix(&request("dev"), "/dev/528").is_ok());
+        assert!(state.authorize_prefix(&request("key"), "/dev/528").is_ok());
+        assert!(state.authorize_prefix(&request("dev"), "").is_err());
+        assert!(state.authorize_prefix(&request("dev"), "/prod").is_err());
+        assert!(state.authorize_admin(&request("dev")).is_err());
+        assert!(state.is_admin_secret("/dev/528", "dev"));
+        assert!(!state.is_admin_secret("/devices", "dev"));
+    }
+}
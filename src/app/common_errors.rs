@@ -3,63 +3,91 @@ use std::fmt::Display;
 use hyper::StatusCode;
 
 use crate::app::app_error::{AppError, ToBadRequest};
-use crate::misc::ParseParamError;
+use crate::config::ROOM_FULL_RETRY_AFTER;
+use crate::misc::{ActorGone, ParseParamError};
+use crate::model::ErrorCode;
 use crate::service::ServiceError;
 
 #[macro_export]
 macro_rules! error {
-    ($status_code: expr, $message: expr) => {{
-        Err(AppError {
-            code: $status_code,
-            message: Some($message),
-        })
+    ($status_code: expr, $code: expr, $message: expr) => {{
+        Err(AppError::new($status_code, $code, $message))
     }};
     ($message: expr) => {{
-        Err(AppError {
-            code: hyper::StatusCode::BAD_REQUEST,
-            message: Some($message),
-        })
+        Err(AppError::bad_request($message))
     }};
 }
 
-#[inline]
-pub fn error<T>(message: String) -> Result<T, AppError> {
-    Err(AppError::bad_request(message))
-}
-
 #[macro_export]
 macro_rules! not_found {
     ($message: expr) => {{
-        Err(AppError {
-            code: hyper::StatusCode::NOT_FOUND,
-            message: Some($message),
-        })
+        Err(AppError::not_found($message))
     }};
 }
 
 #[inline]
 pub fn not_found<T>() -> Result<T, AppError> {
-    Err(AppError {
-        code: StatusCode::NOT_FOUND,
-        message: Some("Not found".to_string()),
-    })
+    Err(AppError::not_found("Not found".to_string()))
+}
+
+impl From<ServiceError> for AppError {
+    fn from(e: ServiceError) -> Self {
+        let status = match e {
+            ServiceError::RoomNotFound
+            | ServiceError::MessageNotFound
+            | ServiceError::PollNotFound => StatusCode::NOT_FOUND,
+            ServiceError::RoomAlreadyExists
+            | ServiceError::SettingsConflict(_)
+            | ServiceError::NamespaceFull => StatusCode::CONFLICT,
+            ServiceError::InvalidRoomName
+            | ServiceError::InvalidArgument(_)
+            | ServiceError::LimitExceeded(_) => StatusCode::BAD_REQUEST,
+            ServiceError::SecretNotMatch => StatusCode::UNAUTHORIZED,
+            ServiceError::Forbidden => StatusCode::FORBIDDEN,
+            ServiceError::RoomFull | ServiceError::ActorGone => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        let retry_after =
+            matches!(e, ServiceError::RoomFull).then_some(ROOM_FULL_RETRY_AFTER.as_secs());
+        AppError {
+            fields: e.fields(),
+            retry_after,
+            ..AppError::new(status, e.code(), e.message())
+        }
+    }
+}
+
+/// Lets handlers use `?` on actor commands.
+impl From<ActorGone> for AppError {
+    fn from(e: ActorGone) -> Self {
+        ServiceError::from(e).into()
+    }
 }
 
 impl<T> ToBadRequest<T> for Result<T, ServiceError> {
     fn to_bad_request(self) -> Result<T, AppError> {
-        self.map_err(|e| match e {
-            ServiceError::RoomNotFound => AppError::not_found("Room not found".to_string()),
-            ServiceError::SecretNotMatch => AppError::secret(),
-        })
+        self.map_err(AppError::from)
     }
 }
 
 impl<T> ToBadRequest<T> for Result<T, ParseParamError<'static>> {
     fn to_bad_request(self) -> Result<T, AppError> {
         self.map_err(|e| match e {
-            ParseParamError::FieldRequired { name } => {
-                AppError::bad_request(format!("{name} is required."))
-            }
+            ParseParamError::FieldRequired { name } => AppError {
+                fields: vec![name],
+                ..AppError::new(
+                    StatusCode::BAD_REQUEST,
+                    ErrorCode::MissingArgument,
+                    format!("{name} is required."),
+                )
+            },
+            ParseParamError::InvalidValue { name } => AppError {
+                fields: vec![name],
+                ..AppError::new(
+                    StatusCode::BAD_REQUEST,
+                    ErrorCode::InvalidArgument,
+                    format!("{name} is invalid."),
+                )
+            },
         })
     }
 }
@@ -1,83 +1,157 @@
 use std::convert::Infallible;
+use std::sync::atomic::Ordering;
 
+use chrono::{DateTime, Duration, Utc};
 use http_body_util::Full;
 use hyper::Response;
+use hyper::header::HeaderValue;
 use serde_json::json;
 use tokio::runtime::Handle;
 
 use crate::{json_response, log};
 use crate::app::app_error::{AppError, ToBadRequest};
+use crate::app::app_state::AppState;
 use crate::app::common_errors::not_found;
-use crate::misc::{empty_body, HttpRequest, HttpResponse, ok_response, Params, StringExt};
+use crate::app::request_params::read_params;
+use crate::app::router::{OPENAPI, route, Route};
+use crate::config::{
+    ADMIN_PAGE_MAX, ADMIN_PAGE_SIZE, PARTICIPANTS_PAGE_MAX, ROOM_MAX_TTL, TOTAL_COUNT_HEADER,
+};
+use crate::misc::{empty_body, HttpRequest, HttpResponse, ok_response};
 use crate::model::{
-    CreateParams, DestroyParams, JoinParams, LastAnnouncementParams, PhotoParams, Room,
+    AdminParams, BulkAnnounceParams, BulkDestroyParams, ClosePollParams, CreateParams,
+    DestroyParams, HistoryParams, JoinParams, LastAnnouncementParams, ParticipantsParams,
+    PhotoParams, PinParams, PollParams, ReadParams, Room, RoomUpdate, RotateSecretParams,
+    ThreadParams, UpdateParams,
 };
-use crate::service::ChatService;
+use crate::service::{ChatService, DROPPED_EVENTS, ServiceError, SLOW_CONSUMERS};
 
 pub async fn default_handler(
-    service: &ChatService,
+    state: &AppState,
     req: HttpRequest,
 ) -> Result<HttpResponse, Infallible> {
-    match handle_request(service, req).await {
+    match handle_request(state, req).await {
         Ok(res) => Ok(res),
         Err(error) => {
-            let status_code = error.code;
+            let status_code = error.status;
             let json = serde_json::to_string(&error).unwrap();
             log!("Err {status_code}: {json}");
-            Ok(Response::builder()
+            let mut builder = Response::builder()
                 .status(status_code)
-                .header(hyper::header::CONTENT_TYPE, "application/json")
-                .body(Full::new(json.into()))
-                .unwrap())
+                .header(hyper::header::CONTENT_TYPE, "application/json");
+            if !error.allow.is_empty() {
+                let allow: Vec<&str> = error.allow.iter().map(|e| e.as_str()).collect();
+                builder = builder.header(hyper::header::ALLOW, allow.join(", "));
+            }
+            if let Some(retry_after) = error.retry_after {
+                builder = builder.header(hyper::header::RETRY_AFTER, retry_after);
+            }
+            Ok(builder.body(Full::new(json.into())).unwrap())
         }
     }
 }
 
 pub async fn handle_request(
-    service: &ChatService,
-    req: HttpRequest,
+    state: &AppState,
+    mut req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
-    let uri = req.uri();
-    let action = uri.path().substring_after_last('/').to_string();
-    let room = uri.path().substring_before_last('/').to_string();
-    log!("{}: {uri}", req.method());
-    match action.as_str() {
-        "" => not_found(),
-        "create" => {
-            if room.is_empty() {
-                not_found()
-            } else {
-                let params = Params::parse_uri(uri).to_bad_request()?;
-                create_room(service, req, room, params).await
-            }
+    log!("{}: {}", req.method(), req.uri());
+    let service = &state.service;
+    match route(req.method(), req.uri().path())? {
+        Route::Status => {
+            state.authorize_status(&req)?;
+            Ok(dump_status(service, req).await)
         }
-        "destroy" => {
-            if room.is_empty() {
-                not_found()
-            } else {
-                let params = DestroyParams::parse_uri(req.uri()).to_bad_request()?;
-                destroy_room(service, req, room, params).await
-            }
+        Route::Debug => {
+            state.authorize_status(&req)?;
+            let metrics = Handle::current().metrics().num_alive_tasks();
+
+            Ok(json_response!({
+                "tasks": metrics,
+                "droppedEvents": DROPPED_EVENTS.load(Ordering::Relaxed),
+                "slowConsumers": SLOW_CONSUMERS.load(Ordering::Relaxed),
+            }))
         }
-        "status" => {
-            if room.is_empty() {
-                Ok(dump_status(service, req).await)
-            } else {
-                room_action(service, req, room, action).await
-            }
+        Route::OpenApi => Ok(json_response(OPENAPI.to_string())),
+        route @ (Route::AdminRooms
+        | Route::AdminStatus
+        | Route::AdminDestroy
+        | Route::AdminAnnounce) => admin_action(state, req, route).await,
+        Route::Create(room) => {
+            let params = read_params(&mut req).await?;
+            create_room(service, req, room, params).await
+        }
+        Route::Destroy(room) => {
+            let params: DestroyParams = read_params(&mut req).await?;
+            destroy_room(state, req, room, params).await
         }
-        "debug" => {
-            if room.is_empty() {
-                let metrics = Handle::current().metrics().num_alive_tasks();
+        Route::Room(room, action) => room_action(state, req, room, action).await,
+    }
+}
 
-                Ok(json_response!({
-                    "tasks": metrics
-                }))
-            } else {
-                not_found()
-            }
+/// matches /v1/admin/*, authenticated by the admin key, or the admin secret of a namespace
+/// covering `prefix`, instead of room secrets
+async fn admin_action(
+    state: &AppState,
+    mut req: HttpRequest,
+    route: Route,
+) -> Result<HttpResponse, AppError> {
+    let service = &state.service;
+    match route {
+        Route::AdminRooms => {
+            let params: AdminParams = read_params(&mut req).await?;
+            state.authorize_prefix(&req, &params.prefix)?;
+            let limit = page_limit(params.limit)?;
+            let rooms = service.rooms(&params.prefix).await;
+            let page: Vec<&String> = rooms
+                .iter()
+                .skip(params.offset)
+                .take(limit)
+                .map(|(e, _)| e)
+                .collect();
+            Ok(json_response!({
+                "total": rooms.len(),
+                "rooms": page,
+            }))
+        }
+        Route::AdminStatus => {
+            let params: AdminParams = read_params(&mut req).await?;
+            state.authorize_prefix(&req, &params.prefix)?;
+            let limit = page_limit(params.limit)?;
+            let (total, rooms) = service
+                .status_page(&params.prefix, params.offset, limit)
+                .await;
+            Ok(json_response!({
+                "total": total,
+                "rooms": rooms,
+            }))
+        }
+        Route::AdminDestroy => {
+            let params: BulkDestroyParams = read_params(&mut req).await?;
+            state.authorize_prefix(&req, &params.prefix)?;
+            Ok(json_response!({
+                "rooms": service.destroy_rooms(&params.prefix).await,
+            }))
+        }
+        Route::AdminAnnounce => {
+            let params: BulkAnnounceParams = read_params(&mut req).await?;
+            state.authorize_prefix(&req, &params.prefix)?;
+            let count = service
+                .announce(&params.prefix, &params.r#type, &params.text)
+                .await;
+            Ok(json_response!({
+                "rooms": count,
+            }))
         }
-        &_ => room_action(service, req, room, action).await,
+        _ => not_found(),
+    }
+}
+
+fn page_limit(limit: Option<usize>) -> Result<usize, AppError> {
+    match limit {
+        None => Ok(ADMIN_PAGE_SIZE),
+        Some(limit) if limit > ADMIN_PAGE_MAX => Err(ServiceError::LimitExceeded("limit").into()),
+        Some(limit) => Ok(limit),
     }
 }
 
@@ -88,69 +162,201 @@ async fn create_room(
     room: String,
     params: CreateParams,
 ) -> Result<HttpResponse, AppError> {
-    service
-        .op
-        .CreateRoom(Room {
-            uid: room,
-            secret: params.secret,
-            post: params.post,
-            post_types: params.post_types,
-        })
-        .await
-        .to_bad_request()?;
-    Ok(ok_response())
+    let room = Room {
+        uid: room,
+        secret: params.secret,
+        post: params.post,
+        post_types: params.post_types,
+        slow_consumer: params.slow_consumer.unwrap_or_default(),
+        slow_mode: params.slow_mode.unwrap_or(0),
+        expires_at: params.ttl.map(expires_at).transpose()?.flatten(),
+        idle_timeout: params.idle_timeout.filter(|e| *e > 0),
+        max_participants: params.max_participants.filter(|e| *e > 0),
+        overflow: params.overflow.unwrap_or_default(),
+        presence_threshold: params.presence_threshold.filter(|e| *e > 0),
+    };
+    let (chat_room, created) = service.create_room(room, params.mode).await?;
+    Ok(json_response!({
+        "created": created,
+        "room": chat_room.op.Status().await?,
+    }))
+}
+
+/// A `ttl` of 0 means no expiry.
+fn expires_at(ttl: u64) -> Result<Option<DateTime<Utc>>, AppError> {
+    if ttl > ROOM_MAX_TTL.as_secs() {
+        return Err(ServiceError::LimitExceeded("ttl").into());
+    }
+    Ok(Some(ttl)
+        .filter(|e| *e > 0)
+        .map(|e| Utc::now() + Duration::seconds(e as i64)))
 }
 
 /// matches /path/to/room/destroy
 async fn destroy_room(
-    service: &ChatService,
+    state: &AppState,
     _: HttpRequest,
     room: String,
     params: DestroyParams,
 ) -> Result<HttpResponse, AppError> {
-    service
-        .op
-        .DestroyRoom(room, params.secret)
-        .await
+    let secret = Some(params.secret).filter(|e| !state.is_admin_secret(&room, e));
+    state
+        .service
+        .shard(&room)
+        .DestroyRoom(room, secret)
+        .await?
         .to_bad_request()?;
     Ok(ok_response())
 }
 /// matches /status
 async fn dump_status(service: &ChatService, _: HttpRequest) -> HttpResponse {
-    let status = service.op.Status().await;
+    let status = service.status().await;
     json_response!(status)
 }
 
 /// matches /path/to/room/other_action
 async fn room_action(
-    service: &ChatService,
-    req: HttpRequest,
+    state: &AppState,
+    mut req: HttpRequest,
     room: String,
     action: String,
 ) -> Result<HttpResponse, AppError> {
-    let chat_room = service.op.GetRoom(room).await.to_bad_request()?;
+    let chat_room = state
+        .service
+        .shard(&room)
+        .GetRoom(room.clone())
+        .await?
+        .to_bad_request()?;
 
     match action.as_str() {
         "join" => {
-            let params = JoinParams::parse_uri(req.uri()).to_bad_request()?;
+            let params: JoinParams = read_params(&mut req).await?;
+            chat_room.op.CanJoin(params.resume.clone()).await??;
             chat_room.join(req, params).await.to_bad_request()
         }
         "count" => Ok(json_response!({
-            "count": chat_room.op.Count().await,
+            "count": chat_room.op.Count().await?,
         })),
-        "status" => Ok(json_response!(chat_room.op.Status().await)),
+        "status" => Ok(json_response!(chat_room.op.Status().await?)),
         "lastAnnouncement" => {
-            let params = LastAnnouncementParams::parse_uri(req.uri()).to_bad_request()?;
-            let announcements = chat_room.op.LastAnnouncement(params.types).await;
+            let params: LastAnnouncementParams = read_params(&mut req).await?;
+            let announcements = chat_room.op.LastAnnouncement(params.types).await?;
             Ok(json_response!(announcements))
         }
+        "history" => {
+            let params: HistoryParams = read_params(&mut req).await?;
+            let history = chat_room.op.History(params.limit).await?;
+            Ok(json_response!(history))
+        }
+        "thread" => {
+            let params: ThreadParams = read_params(&mut req).await?;
+            match chat_room.op.Thread(params.id).await? {
+                None => Err(ServiceError::MessageNotFound.into()),
+                Some(thread) => Ok(json_response!(thread)),
+            }
+        }
+        "pinned" => Ok(json_response!(chat_room.op.Pinned().await?)),
+        "pin" => {
+            let params: PinParams = read_params(&mut req).await?;
+            if !state.is_room_secret(&room, &chat_room, &params.secret) {
+                return Err(AppError::secret());
+            }
+            chat_room
+                .op
+                .Pin(params.id, params.expires_in)
+                .await?
+                .to_bad_request()?;
+            Ok(ok_response())
+        }
+        "unpin" => {
+            let params: PinParams = read_params(&mut req).await?;
+            if !state.is_room_secret(&room, &chat_room, &params.secret) {
+                return Err(AppError::secret());
+            }
+            chat_room.op.Unpin(params.id).await?.to_bad_request()?;
+            Ok(ok_response())
+        }
+        "polls" => Ok(json_response!(chat_room.op.Polls().await?)),
+        "poll" => {
+            let params: PollParams = read_params(&mut req).await?;
+            if !state.is_room_secret(&room, &chat_room, &params.secret) {
+                return Err(AppError::secret());
+            }
+            let poll = chat_room
+                .op
+                .OpenPoll(
+                    params.question,
+                    params.options,
+                    params.duration,
+                    params.multiple,
+                    params.answers,
+                )
+                .await?
+                .to_bad_request()?;
+            Ok(json_response!(poll))
+        }
+        "closePoll" => {
+            let params: ClosePollParams = read_params(&mut req).await?;
+            if !state.is_room_secret(&room, &chat_room, &params.secret) {
+                return Err(AppError::secret());
+            }
+            chat_room.op.ClosePoll(params.id).await?.to_bad_request()?;
+            Ok(ok_response())
+        }
+        "update" => {
+            let params: UpdateParams = read_params(&mut req).await?;
+            if !state.is_room_secret(&room, &chat_room, &params.secret) {
+                return Err(AppError::secret());
+            }
+            let update = RoomUpdate {
+                secret: None,
+                post: params.post,
+                post_types: params.post_types,
+                slow_consumer: params.slow_consumer,
+                slow_mode: params.slow_mode,
+                expires_at: params.ttl.map(expires_at).transpose()?,
+                idle_timeout: params.idle_timeout,
+                max_participants: params.max_participants,
+                overflow: params.overflow,
+                presence_threshold: params.presence_threshold,
+            };
+            chat_room.op.Update(update).await?;
+            Ok(ok_response())
+        }
+        "rotateSecret" => {
+            let params: RotateSecretParams = read_params(&mut req).await?;
+            if !state.is_room_secret(&room, &chat_room, &params.secret) {
+                return Err(AppError::secret());
+            }
+            if params.new_secret.is_empty() {
+                return Err(ServiceError::InvalidArgument("newSecret").into());
+            }
+            chat_room.op.RotateSecret(params.new_secret).await?;
+            Ok(ok_response())
+        }
+        "read" => {
+            let params: ReadParams = read_params(&mut req).await?;
+            let markers = chat_room.op.ReadMarkers(params.username).await?;
+            Ok(json_response!(markers))
+        }
         "participants" => {
-            let participants = chat_room.op.Participants().await;
-            Ok(json_response!(participants))
+            let params: ParticipantsParams = read_params(&mut req).await?;
+            if params.limit.is_some_and(|e| e > PARTICIPANTS_PAGE_MAX) {
+                return Err(ServiceError::LimitExceeded("limit").into());
+            }
+            let (total, participants) = chat_room
+                .op
+                .Participants(params.search, params.offset, params.limit)
+                .await?;
+            let mut response = json_response!(participants);
+            response
+                .headers_mut()
+                .insert(TOTAL_COUNT_HEADER, HeaderValue::from(total));
+            Ok(response)
         }
         "photo" => {
-            let params = PhotoParams::parse_uri(req.uri()).to_bad_request()?;
-            let photo = chat_room.op.Photo(params.username).await;
+            let params: PhotoParams = read_params(&mut req).await?;
+            let photo = chat_room.op.Photo(params.username).await?;
             match photo {
                 None => not_found(),
                 Some(url) => Ok(Response::builder()
@@ -1,3 +1,6 @@
 mod app_error;
+pub mod app_state;
 mod common_errors;
 pub mod handlers;
+mod request_params;
+mod router;
@@ -0,0 +1,119 @@
+use std::error::Error;
+
+use http_body_util::{BodyExt, Limited};
+use hyper::{Method, Request};
+use hyper::body::Body;
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::app::app_error::{AppError, ToBadRequest};
+use crate::config::MAX_BODY_SIZE;
+use crate::misc::{Params, QueryParams};
+
+/// Params of an action, from the JSON body of a `POST`, `PATCH` or `DELETE`, from the query
+/// string otherwise.
+///
+/// The secret may also come from an `Authorization: Bearer <secret>` header, which keeps it
+/// out of access logs.
+pub async fn read_params<T, B>(req: &mut Request<B>) -> Result<T, AppError>
+where
+    T: Params + DeserializeOwned,
+    B: Body + Unpin,
+    B::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    let secret = bearer(req);
+    if req.method() != Method::GET && is_json(req) {
+        let body = Limited::new(req.body_mut(), MAX_BODY_SIZE)
+            .collect()
+            .await
+            .to_bad_request()?
+            .to_bytes();
+        let mut value: Value = serde_json::from_slice(&body).to_bad_request()?;
+        if let (Some(secret), Value::Object(object)) = (secret, &mut value) {
+            object
+                .entry("secret")
+                .or_insert_with(|| Value::String(secret));
+        }
+        serde_json::from_value(value).to_bad_request()
+    } else {
+        let mut params = QueryParams::parse(req.uri().query());
+        if let Some(secret) = secret {
+            params.set_default("secret", secret);
+        }
+        T::parse(&params).to_bad_request()
+    }
+}
+
+/// The token of an `Authorization: Bearer <token>` header, other schemes are ignored.
+pub fn bearer<B>(req: &Request<B>) -> Option<String> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    value.strip_prefix("Bearer ").map(str::to_string)
+}
+
+fn is_json<B>(req: &Request<B>) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|e| e.to_str().ok())
+        .is_some_and(|e| e.starts_with("application/json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::Full;
+    use hyper::body::Bytes;
+    use hyper::Method;
+
+    use super::*;
+    use crate::model::{CreateParams, DestroyParams};
+
+    fn request(method: Method, uri: &str, body: &str) -> Request<Full<Bytes>> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .header(AUTHORIZATION, "Bearer s3cret")
+            .body(Full::new(Bytes::from(body.to_string())))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_json_body() {
+        let body =
+            r#"{"postTypes":["message","announcement"],"slowConsumer":"disconnect","ttl":60}"#;
+        let mut req = request(Method::POST, "/app/room/create", body);
+        let params: CreateParams = read_params(&mut req).await.unwrap();
+        assert_eq!(params.secret, "s3cret");
+        assert_eq!(params.post_types, ["message", "announcement"]);
+        assert_eq!(params.ttl, Some(60));
+
+        let mut req = request(Method::POST, "/app/room/create", r#"{"ttl":"60"}"#);
+        let result: Result<CreateParams, _> = read_params(&mut req).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_with_header() {
+        let mut req = request(Method::GET, "/app/room/destroy", "");
+        let params: DestroyParams = read_params(&mut req).await.unwrap();
+        assert_eq!(params.secret, "s3cret");
+
+        let mut req = request(Method::GET, "/app/room/destroy?secret=other", "");
+        let params: DestroyParams = read_params(&mut req).await.unwrap();
+        assert_eq!(params.secret, "other");
+    }
+
+    #[test]
+    fn test_bearer() {
+        let req = request(Method::GET, "/app/room/destroy", "");
+        assert_eq!(bearer(&req).as_deref(), Some("s3cret"));
+
+        let mut req = request(Method::GET, "/app/room/destroy", "");
+        let basic = "Basic czNjcmV0".parse().unwrap();
+        req.headers_mut().insert(AUTHORIZATION, basic);
+        assert_eq!(bearer(&req), None);
+
+        req.headers_mut().insert(AUTHORIZATION, "s3cret".parse().unwrap());
+        assert_eq!(bearer(&req), None);
+    }
+}
@@ -0,0 +1,189 @@
+use hyper::Method;
+use urlencoding::decode;
+
+use crate::app::app_error::AppError;
+use crate::misc::StringExt;
+
+/// Describes the `/v1` API, served at `/v1/openapi.json`.
+pub const OPENAPI: &str = include_str!("openapi.json");
+
+/// What a request asks for.
+#[derive(Debug, PartialEq)]
+pub enum Route {
+    Status,
+    Debug,
+    OpenApi,
+    AdminRooms,
+    AdminStatus,
+    AdminDestroy,
+    AdminAnnounce,
+    Create(String),
+    Destroy(String),
+    /// Any other action of a room, e.g. `join` or `update`.
+    Room(String, String),
+}
+
+/// Actions of `/v1/rooms/{uid}/{action}`, the room itself is a resource of its own.
+const ROOM_ACTIONS: [(&str, Method); 15] = [
+    ("join", Method::GET),
+    ("count", Method::GET),
+    ("lastAnnouncement", Method::GET),
+    ("history", Method::GET),
+    ("thread", Method::GET),
+    ("pinned", Method::GET),
+    ("polls", Method::GET),
+    ("read", Method::GET),
+    ("participants", Method::GET),
+    ("photo", Method::GET),
+    ("pin", Method::POST),
+    ("unpin", Method::POST),
+    ("poll", Method::POST),
+    ("closePoll", Method::POST),
+    ("rotateSecret", Method::POST),
+];
+
+pub fn route(method: &Method, path: &str) -> Result<Route, AppError> {
+    match path.strip_prefix("/v1/") {
+        Some(path) => route_v1(method, path),
+        None => route_legacy(path),
+    }
+}
+
+/// `/v1/rooms/{uid}/...` where `uid` is a single percent-encoded segment, so a room can be
+/// named like an action.
+fn route_v1(method: &Method, path: &str) -> Result<Route, AppError> {
+    let segments: Vec<&str> = path.split('/').collect();
+    match segments.as_slice() {
+        ["status"] => only(method, Method::GET, Route::Status),
+        ["debug"] => only(method, Method::GET, Route::Debug),
+        ["openapi.json"] => only(method, Method::GET, Route::OpenApi),
+        ["admin", "rooms"] => only(method, Method::GET, Route::AdminRooms),
+        ["admin", "status"] => only(method, Method::GET, Route::AdminStatus),
+        ["admin", "destroy"] => only(method, Method::POST, Route::AdminDestroy),
+        ["admin", "announce"] => only(method, Method::POST, Route::AdminAnnounce),
+        ["rooms", uid] => {
+            let room = room_uid(uid)?;
+            match *method {
+                Method::GET => Ok(Route::Room(room, "status".to_string())),
+                Method::POST => Ok(Route::Create(room)),
+                Method::PATCH => Ok(Route::Room(room, "update".to_string())),
+                Method::DELETE => Ok(Route::Destroy(room)),
+                _ => Err(AppError::method_not_allowed(vec![
+                    Method::GET,
+                    Method::POST,
+                    Method::PATCH,
+                    Method::DELETE,
+                ])),
+            }
+        }
+        ["rooms", uid, action] => {
+            let Some((action, allowed)) = ROOM_ACTIONS.iter().find(|(e, _)| e == action) else {
+                return Err(not_found());
+            };
+            let room = room_uid(uid)?;
+            only(
+                method,
+                allowed.clone(),
+                Route::Room(room, action.to_string()),
+            )
+        }
+        _ => Err(not_found()),
+    }
+}
+
+/// `/path/to/room/action`, any method is accepted as before.
+fn route_legacy(path: &str) -> Result<Route, AppError> {
+    let action = path.substring_after_last('/');
+    let room = path.substring_before_last('/').to_string();
+    match action {
+        "" => Err(not_found()),
+        "status" if room.is_empty() => Ok(Route::Status),
+        "debug" if room.is_empty() => Ok(Route::Debug),
+        "debug" | "create" | "destroy" if room.is_empty() => Err(not_found()),
+        "create" => Ok(Route::Create(room)),
+        "destroy" => Ok(Route::Destroy(room)),
+        _ => Ok(Route::Room(room, action.to_string())),
+    }
+}
+
+fn only(method: &Method, allowed: Method, route: Route) -> Result<Route, AppError> {
+    if *method == allowed {
+        Ok(route)
+    } else {
+        Err(AppError::method_not_allowed(vec![allowed]))
+    }
+}
+
+/// The same room as the legacy path `/app/room` is `/v1/rooms/%2Fapp%2Froom`, the leading
+/// slash can be left out.
+fn room_uid(segment: &str) -> Result<String, AppError> {
+    let uid = decode(segment).map_err(|_| AppError::bad_request("uid is invalid.".to_string()))?;
+    match uid.as_ref() {
+        "" | "/" => Err(not_found()),
+        uid if uid.starts_with('/') => Ok(uid.to_string()),
+        uid => Ok(format!("/{uid}")),
+    }
+}
+
+fn not_found() -> AppError {
+    AppError::not_found("Not found".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::StatusCode;
+
+    use super::*;
+
+    fn room(uid: &str, action: &str) -> Route {
+        Route::Room(uid.to_string(), action.to_string())
+    }
+
+    #[test]
+    fn test_legacy_routes() {
+        assert_eq!(route(&Method::GET, "/status").unwrap(), Route::Status);
+        assert_eq!(
+            route(&Method::GET, "/app/room/destroy").unwrap(),
+            Route::Destroy("/app/room".to_string())
+        );
+        assert_eq!(
+            route(&Method::GET, "/app/room/status").unwrap(),
+            room("/app/room", "status")
+        );
+        assert!(route(&Method::GET, "/create").is_err());
+    }
+
+    #[test]
+    fn test_v1_routes() {
+        assert_eq!(
+            route(&Method::DELETE, "/v1/rooms/%2Fapp%2Fstatus").unwrap(),
+            Route::Destroy("/app/status".to_string())
+        );
+        assert_eq!(
+            route(&Method::POST, "/v1/rooms/app%2Froom").unwrap(),
+            Route::Create("/app/room".to_string())
+        );
+        assert_eq!(
+            route(&Method::GET, "/v1/rooms/%2Fapp%2Froom/history").unwrap(),
+            room("/app/room", "history")
+        );
+
+        let error = route(&Method::GET, "/v1/rooms/%2Fapp%2Froom/pin").unwrap_err();
+        assert_eq!(error.status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(error.allow, [Method::POST]);
+        let error = route(&Method::PUT, "/v1/rooms/%2Fapp%2Froom").unwrap_err();
+        assert_eq!(error.status, StatusCode::METHOD_NOT_ALLOWED);
+        let error = route(&Method::GET, "/v1/rooms/%2Fapp/room/history").unwrap_err();
+        assert_eq!(error.status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_openapi() {
+        let document: serde_json::Value = serde_json::from_str(OPENAPI).unwrap();
+        for (action, method) in &ROOM_ACTIONS {
+            let path = format!("/v1/rooms/{{uid}}/{action}");
+            let operation = &document["paths"][&path][method.as_str().to_lowercase()];
+            assert!(operation.is_object(), "{method} {path} is not documented");
+        }
+    }
+}
@@ -1,4 +1,86 @@
 use std::time::Duration;
 
 pub const PORT: u16 = 9339;
-pub const PING_INTERVAL: Duration = Duration::from_secs(120);
+/// How often a room flushes coalesced events such as `typing`.
+pub const ROOM_TICK_INTERVAL: Duration = Duration::from_secs(1);
+/// Minimum delay between two `typing` requests accepted from the same client.
+pub const TYPING_THROTTLE: Duration = Duration::from_secs(3);
+/// Maximum number of users listed by name in a single `typing` event.
+pub const TYPING_MAX_USERS: usize = 3;
+/// Number of recent messages kept by a room for reactions and the `history` action.
+pub const HISTORY_SIZE: usize = 200;
+/// Longest accepted reaction, in bytes (an emoji with modifiers or a short code).
+pub const REACTION_MAX_LEN: usize = 32;
+/// Rooms above this size keep read markers but stop broadcasting `read` events.
+pub const READ_RECEIPTS_MAX_PARTICIPANTS: usize = 50;
+/// Maximum number of messages pinned at the same time in a room.
+pub const PINNED_MAX: usize = 10;
+/// Longest `expiresIn` accepted when pinning a message.
+pub const PIN_MAX_DURATION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Duration of a poll opened without one.
+pub const POLL_DEFAULT_DURATION: Duration = Duration::from_secs(60);
+pub const POLL_MAX_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+pub const POLL_MAX_OPTIONS: usize = 10;
+/// Maximum number of closed polls, with their ballots, kept by a room for the `polls` endpoint.
+pub const POLL_MAX_CLOSED: usize = 20;
+/// Rooms above this size only send the participant count in `welcome`.
+pub const WELCOME_MAX_PARTICIPANTS: usize = 100;
+/// Rooms of this size or more send joins and leaves once per tick in `presence` events, unless
+/// they set their own `presenceThreshold`.
+pub const PRESENCE_THRESHOLD: usize = 200;
+/// Maximum number of joins and leaves listed in a single `presence` event.
+pub const PRESENCE_DELTA_MAX: usize = 100;
+/// Largest page of the `participants` action.
+pub const PARTICIPANTS_PAGE_MAX: usize = 1000;
+/// Header of the `participants` action with the number of matches, all pages included.
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+/// Number of recent room events kept to be replayed to clients who missed them.
+pub const EVENT_LOG_SIZE: usize = 500;
+/// Events waiting to be written to a client before its slow consumer policy applies.
+pub const CLIENT_QUEUE_SIZE: usize = 256;
+/// Room events replayed at once at most, so that they fit in the queue next to the welcome.
+pub const REPLAY_MAX: usize = CLIENT_QUEUE_SIZE - 1;
+/// Close code sent to clients disconnected for not reading fast enough.
+pub const SLOW_CONSUMER_CLOSE_CODE: u16 = 4008;
+/// Close code sent to a connection whose session was resumed by a new one.
+pub const SESSION_RESUMED_CLOSE_CODE: u16 = 4009;
+/// How long `/status` waits for each room before leaving it out.
+pub const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a dropped connection can be resumed before its user is announced as left.
+pub const RESUME_GRACE: Duration = Duration::from_secs(30);
+/// `Retry-After` of a `join` rejected because the room is full.
+pub const ROOM_FULL_RETRY_AFTER: Duration = Duration::from_secs(30);
+/// Longest room uid accepted, in bytes.
+pub const ROOM_UID_MAX_LEN: usize = 256;
+/// Longest `ttl` accepted when creating a room.
+pub const ROOM_MAX_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// Environment variable with the `redis://[[username]:password@]host[:port]` backplane shared by
+/// several instances.
+pub const BACKPLANE_ENV: &str = "CHAT_BACKPLANE";
+pub const BACKPLANE_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// How often an instance republishes its participants of a room to the other instances.
+pub const PRESENCE_REFRESH: Duration = Duration::from_secs(10);
+/// Participants of another instance, and the replica of a room whose instance stopped, are
+/// forgotten after a missed refresh.
+pub const PRESENCE_TTL: Duration = Duration::from_secs(15);
+/// Upper bound of taps accepted in a single `burst` request.
+pub const BURST_MAX_COUNT: usize = 50;
+/// Upper bound of distinct emojis in a `bursts` event, taps on other emojis are dropped.
+pub const BURST_MAX_KINDS: usize = 20;
+/// Largest JSON body accepted by the REST actions, in bytes.
+pub const MAX_BODY_SIZE: usize = 64 * 1024;
+/// Environment variable with the bearer token of the admin endpoints, `/status` and `/debug`.
+/// Without it, they are all disabled.
+pub const ADMIN_KEY_ENV: &str = "CHAT_ADMIN_KEY";
+/// Environment variable set to `true` to serve `/status` and `/debug` without the admin key.
+pub const PUBLIC_STATUS_ENV: &str = "CHAT_PUBLIC_STATUS";
+/// Rooms listed by an admin endpoint when no `limit` is given.
+pub const ADMIN_PAGE_SIZE: usize = 100;
+pub const ADMIN_PAGE_MAX: usize = 1000;
+/// Environment variable with the path of a JSON file listing the namespaces, see [Namespace].
+///
+/// [Namespace]: crate::model::Namespace
+pub const NAMESPACES_ENV: &str = "CHAT_NAMESPACES";
+/// Header of webhook posts with an HMAC-SHA256 of the body by signing key of the namespace,
+/// e.g. `sha256=<hex>, sha256=<hex>`.
+pub const SIGNATURE_HEADER: &str = "X-Chat-Signature";
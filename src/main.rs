@@ -6,10 +6,12 @@ use hyper::service::service_fn;
 use hyper_util::rt::TokioIo;
 use tokio::net::TcpListener;
 
+use crate::app::app_state::AppState;
 use crate::app::handlers::default_handler;
-use crate::config::PORT;
+use crate::config::{ADMIN_KEY_ENV, BACKPLANE_ENV, NAMESPACES_ENV, PORT, PUBLIC_STATUS_ENV};
 use crate::misc::*;
-use crate::service::ChatService;
+use crate::model::Namespaces;
+use crate::service::{Backplane, ChatService, RedisBackplane, Replication};
 
 mod misc;
 mod model;
@@ -18,25 +20,46 @@ mod app;
 mod config;
 mod service;
 
-#[tokio::main(flavor = "current_thread")]
+#[tokio::main]
 async fn main() {
     log!("warn: DEBUG mode");
     let addr = SocketAddr::from(([0, 0, 0, 0], PORT));
     let listener = TcpListener::bind(addr).await.unwrap();
     println!("service is running on: http://{addr}");
 
-    let global_state = Arc::new(ChatService::create());
+    let replication = std::env::var(BACKPLANE_ENV).ok().map(|url| {
+        let backplane = RedisBackplane::connect(&url).unwrap_or_else(|error| panic!("{error}"));
+        let backplane: Arc<dyn Backplane> = Arc::new(backplane);
+        Replication::new(backplane)
+    });
+    let namespaces = match std::env::var(NAMESPACES_ENV) {
+        Ok(path) => Namespaces::load(&path).unwrap_or_else(|error| panic!("{error}")),
+        Err(_) => Namespaces::default(),
+    };
+    let admin_key = std::env::var(ADMIN_KEY_ENV).ok().filter(|e| !e.is_empty());
+    let public_status = std::env::var(PUBLIC_STATUS_ENV).is_ok_and(|e| e == "true");
+    if public_status {
+        println!("warn: {PUBLIC_STATUS_ENV} is set, /status and /debug are public");
+    }
+    if admin_key.is_none() {
+        println!("warn: {ADMIN_KEY_ENV} is not set, the admin endpoints are disabled");
+    }
+    let global_state = Arc::new(AppState {
+        service: ChatService::create(replication, namespaces),
+        admin_key,
+        public_status,
+    });
     loop {
         let (stream, _) = listener.accept().await.unwrap();
 
         // Use an adapter to access something implementing `tokio::io` traits as if they implement
         // `hyper::rt` IO traits.
         let io = TokioIo::new(stream);
-        let service = global_state.clone();
+        let state = global_state.clone();
         // Spawn a tokio task to serve multiple connections concurrently
         tokio::spawn(async move {
             if let Err(err) = http1::Builder::new()
-                .serve_connection(io, service_fn(|req| default_handler(&service, req)))
+                .serve_connection(io, service_fn(|req| default_handler(&state, req)))
                 // Support WS upgradable protocol
                 .with_upgrades()
                 .await
@@ -1,3 +1,7 @@
+/// The actor behind a `CommandSender` has stopped, e.g. a destroyed room.
+#[derive(Debug)]
+pub struct ActorGone;
+
 #[macro_export]
 macro_rules! command {
     (
@@ -22,12 +26,12 @@ macro_rules! command {
         }
         impl CommandSender {
         $(
-            #[allow(non_snake_case,unused)]
-            $vis async fn $name (&self, $($param: $input,)*) $(-> $output)? {
+            #[allow(non_snake_case,unused,unused_parens)]
+            $vis async fn $name (&self, $($param: $input,)*) -> Result<($($output)?), $crate::misc::ActorGone> {
                 let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
                 let data = Command::$name{$($param,)* resp_tx};
-                self.tx.send(data).await.unwrap();
-                resp_rx.await.unwrap()
+                self.tx.send(data).await.map_err(|_| $crate::misc::ActorGone)?;
+                resp_rx.await.map_err(|_| $crate::misc::ActorGone)
             }
         )+
         }
@@ -42,17 +46,25 @@ macro_rules! command {
                 let data = Command::$name{$($param,)* resp_tx};
                 let tx = self.tx;
                 tokio::spawn(async move{
-                    tx.send(data).await.unwrap();
-                    let _ = resp_rx.await.unwrap();
+                    if tx.send(data).await.is_err() || resp_rx.await.is_err() {
+                        $crate::log!("`{}` not handled, the actor is gone", stringify!($name));
+                    }
                 });
             }
         )+
         }
 
         impl CommandSender {
+            #[allow(unused)]
             pub fn spawn(&self) -> SpawnCommandSender {
                 SpawnCommandSender {tx: self.tx.clone() }
             }
+
+            /// Whether the actor has stopped.
+            #[allow(unused)]
+            pub fn is_closed(&self) -> bool {
+                self.tx.is_closed()
+            }
         }
 
         impl Command {
@@ -32,3 +32,56 @@ where
         Err(e) => Err(serde::de::Error::custom(e)),
     }
 }
+
+/// Same format for optional dates, use along with `skip_serializing_if = "Option::is_none"`.
+pub mod option {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            None => serializer.serialize_none(),
+            Some(date) => super::serialize(date, serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super")] DateTime<Utc>);
+
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|e| e.0))
+    }
+}
+
+/// Same format for dates that can be removed: a missing field is `None` and `null` is
+/// `Some(None)`. Use along with `default` and `skip_serializing_if = "Option::is_none"`.
+pub mod nullable {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S>(
+        date: &Option<Option<DateTime<Utc>>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            None => serializer.serialize_none(),
+            Some(date) => super::option::serialize(date, serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Option<DateTime<Utc>>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::option::deserialize(deserializer).map(Some)
+    }
+}
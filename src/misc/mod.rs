@@ -1,16 +1,20 @@
-use futures::stream::{SplitSink, SplitStream};
+use std::pin::Pin;
+
+use futures::Sink;
 use http_body_util::Full;
 use hyper::{Request, Response};
 use hyper::body::{Bytes, Incoming};
-use hyper_tungstenite::HyperWebsocketStream;
-use hyper_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::{Error, Message};
 
+pub use command::ActorGone;
 pub use option_ext::OptionExt;
 pub use query_params::{Params, ParseParamError, QueryParams};
 pub use response::*;
 pub use string_ext::{OrEmpty, StringExt};
+pub use token::{is_same_token, random_token};
 
 pub mod date_serde;
+pub mod websocket;
 
 mod command;
 mod log;
@@ -18,10 +22,11 @@ mod option_ext;
 mod query_params;
 mod response;
 mod string_ext;
+mod token;
 
 pub type HttpRequest = Request<Incoming>;
 
 pub type HttpResponse = Response<Full<Bytes>>;
 
-pub type WebSocketSink = SplitSink<HyperWebsocketStream, Message>;
-pub type WebSocketStream = SplitStream<HyperWebsocketStream>;
+/// Write half of a client's WebSocket, boxed so that tests can join rooms without a socket.
+pub type WebSocketSink = Pin<Box<dyn Sink<Message, Error = Error> + Send>>;
@@ -1,14 +1,18 @@
-use hyper::Uri;
-use querystring::{querify, QueryParam};
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use querystring::querify;
 use urlencoding::decode;
 
 pub struct QueryParams<'a> {
-    vec: Vec<QueryParam<'a>>,
+    // decoded values
+    vec: Vec<(&'a str, Cow<'a, str>)>,
 }
 
 #[derive(Debug)]
 pub enum ParseParamError<'a> {
     FieldRequired { name: &'a str },
+    InvalidValue { name: &'a str },
 }
 
 macro_rules! empty_vec {
@@ -21,7 +25,19 @@ impl<'a> QueryParams<'a> {
     pub fn parse(query: Option<&'a str>) -> QueryParams<'a> {
         match query {
             None => QueryParams { vec: empty_vec!() },
-            Some(s) => QueryParams { vec: querify(s) },
+            Some(s) => QueryParams {
+                vec: querify(s)
+                    .into_iter()
+                    .filter_map(|(key, value)| Some((key, decode(value).ok()?)))
+                    .collect(),
+            },
+        }
+    }
+
+    /// Adds a param taken from elsewhere, e.g. a header, unless the query has it.
+    pub fn set_default(&mut self, name: &'a str, value: String) {
+        if self.get(name).is_none() {
+            self.vec.push((name, Cow::Owned(value)));
         }
     }
 
@@ -29,8 +45,7 @@ impl<'a> QueryParams<'a> {
         self.vec
             .iter()
             .find(|(key, _)| key == &name)
-            .and_then(|(_, value)| decode(value).ok())
-            .map(|e| e.into_owned())
+            .map(|(_, value)| value.to_string())
     }
 
     pub fn require<'b>(&self, name: &'b str) -> Result<String, ParseParamError<'b>> {
@@ -40,15 +55,24 @@ impl<'a> QueryParams<'a> {
         }
     }
 
+    pub fn get_parsed<'b, T: FromStr>(
+        &self,
+        name: &'b str,
+    ) -> Result<Option<T>, ParseParamError<'b>> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| ParseParamError::InvalidValue { name }),
+        }
+    }
+
     pub fn get_list(&self, name: &str) -> Vec<String> {
-        self.vec
-            .iter()
-            .find(|(key, _)| key == &name)
-            .and_then(|(_, a)| decode(a).ok())
-            .map_or_else(
-                || empty_vec!(),
-                |s| s.split(',').map(|e| e.to_owned()).collect(),
-            )
+        self.vec.iter().find(|(key, _)| key == &name).map_or_else(
+            || empty_vec!(),
+            |(_, s)| s.split(',').map(|e| e.to_owned()).collect(),
+        )
     }
 }
 
@@ -56,21 +80,4 @@ pub trait Params {
     fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>>
     where
         Self: Sized;
-
-    fn parse_uri<'a>(uri: &Uri) -> Result<Self, ParseParamError<'a>>
-    where
-        Self: Sized,
-    {
-        Self::parse(&QueryParams::parse(uri.query()))
-    }
-}
-
-pub trait GetQueryFromUri {
-    fn query_params(&self) -> QueryParams;
-}
-
-impl GetQueryFromUri for Uri {
-    fn query_params(&self) -> QueryParams {
-        QueryParams::parse(self.query())
-    }
 }
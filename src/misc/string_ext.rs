@@ -27,10 +27,10 @@ pub trait OrEmpty {
 
 impl OrEmpty for Option<String> {
     fn or_empty(&self) -> &str {
-        return match self {
+        match self {
             None => "",
             Some(value) => value,
-        };
+        }
     }
 }
 
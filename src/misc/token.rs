@@ -0,0 +1,18 @@
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use subtle::ConstantTimeEq;
+
+/// A random alphanumeric string, hard enough to guess to be used as a credential.
+pub fn random_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// Compares credentials in constant time, so that response times don't tell how much of a guess
+/// was right.
+pub fn is_same_token(a: &str, b: &str) -> bool {
+    a.as_bytes().ct_eq(b.as_bytes()).into()
+}
@@ -0,0 +1,159 @@
+use hyper::{Response, StatusCode};
+use hyper::header::{HeaderName, CONNECTION, UPGRADE};
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use hyper_util::rt::TokioIo;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::error::ProtocolError;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+
+use crate::misc::{empty_body, HttpRequest, HttpResponse};
+
+pub type WebSocket = WebSocketStream<TokioIo<Upgraded>>;
+
+/// Whether the request asks to switch to the WebSocket protocol.
+pub fn is_upgrade_request(req: &HttpRequest) -> bool {
+    has_token(req, CONNECTION, "upgrade") && has_token(req, UPGRADE, "websocket")
+}
+
+fn has_token(req: &HttpRequest, name: HeaderName, token: &str) -> bool {
+    req.headers()
+        .get_all(name)
+        .iter()
+        .filter_map(|e| e.to_str().ok())
+        .flat_map(|e| e.split(','))
+        .any(|e| e.trim().eq_ignore_ascii_case(token))
+}
+
+/// Answers the handshake, the socket can be accepted once the response is sent.
+pub fn upgrade(req: &mut HttpRequest) -> Result<(HttpResponse, OnUpgrade), ProtocolError> {
+    let key = req
+        .headers()
+        .get("Sec-WebSocket-Key")
+        .ok_or(ProtocolError::MissingSecWebSocketKey)?;
+    if req
+        .headers()
+        .get("Sec-WebSocket-Version")
+        .map(|e| e.as_bytes())
+        != Some(b"13")
+    {
+        return Err(ProtocolError::MissingSecWebSocketVersionHeader);
+    }
+    let response = Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(CONNECTION, "upgrade")
+        .header(UPGRADE, "websocket")
+        .header("Sec-WebSocket-Accept", derive_accept_key(key.as_bytes()))
+        .body(empty_body())
+        .unwrap();
+    Ok((response, hyper::upgrade::on(req)))
+}
+
+pub async fn accept(on_upgrade: OnUpgrade) -> hyper::Result<WebSocket> {
+    let upgraded = on_upgrade.await?;
+    Ok(WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use futures::{SinkExt, StreamExt};
+    use http_body_util::Empty;
+    use hyper::Request;
+    use hyper::body::{Bytes, Incoming};
+    use hyper::client::conn::http1 as client;
+    use hyper::server::conn::http1 as server;
+    use hyper::service::service_fn;
+    use tokio::io::DuplexStream;
+    use tokio_tungstenite::tungstenite::Message;
+
+    use super::*;
+
+    /// Serves one connection that echoes the first message of the socket.
+    fn serve() -> DuplexStream {
+        let (client, server) = tokio::io::duplex(1024);
+        let service = service_fn(|mut req: HttpRequest| async move {
+            if !is_upgrade_request(&req) {
+                return Ok::<_, Infallible>(bad_request());
+            }
+            let Ok((response, on_upgrade)) = upgrade(&mut req) else {
+                return Ok(bad_request());
+            };
+            tokio::spawn(async move {
+                let mut socket = accept(on_upgrade).await.unwrap();
+                if let Some(Ok(message)) = socket.next().await {
+                    socket.send(message).await.unwrap();
+                }
+            });
+            Ok(response)
+        });
+        tokio::spawn(
+            server::Builder::new()
+                .serve_connection(TokioIo::new(server), service)
+                .with_upgrades(),
+        );
+        client
+    }
+
+    fn bad_request() -> HttpResponse {
+        Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(empty_body())
+            .unwrap()
+    }
+
+    async fn handshake(headers: &[(&str, &str)]) -> Response<Incoming> {
+        let (mut sender, connection) = client::handshake(TokioIo::new(serve())).await.unwrap();
+        tokio::spawn(connection.with_upgrades());
+        let mut req = Request::builder().uri("/app/room/join");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        sender
+            .send_request(req.body(Empty::<Bytes>::new()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handshake() {
+        // the sample handshake of RFC 6455
+        let headers = [
+            ("Connection", "keep-alive, Upgrade"),
+            ("Upgrade", "websocket"),
+            ("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="),
+            ("Sec-WebSocket-Version", "13"),
+        ];
+        let response = handshake(&headers).await;
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(
+            response.headers()["Sec-WebSocket-Accept"],
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+
+        let response = handshake(&headers[1..]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = handshake(&[headers[0], headers[1], headers[3]]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let mut old_version = headers;
+        old_version[3].1 = "8";
+        let response = handshake(&old_version).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_echo() {
+        let (mut socket, response) =
+            tokio_tungstenite::client_async("ws://localhost/app/room/join", serve())
+                .await
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+
+        socket.send(Message::text("hello")).await.unwrap();
+        let message = socket.next().await.unwrap().unwrap();
+        assert_eq!(message.to_text().unwrap(), "hello");
+    }
+}
@@ -0,0 +1,34 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+
+/// A message kept in the room history.
+#[derive(Debug, Serialize, Clone)]
+pub struct ChatMessage {
+    pub id: u64,
+    pub from: String,
+    pub display: String,
+    #[serde(with = "crate::misc::date_serde")]
+    pub date: DateTime<Utc>,
+    pub text: String,
+    pub r#type: String,
+    #[serde(rename = "replyTo", skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<u64>,
+    /// Id of the first message of the thread this message replies to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread: Option<u64>,
+    /// Usernames who reacted, by emoji. Only the counts are exposed.
+    #[serde(serialize_with = "serialize_reactions")]
+    pub reactions: BTreeMap<String, HashSet<String>>,
+}
+
+fn serialize_reactions<S>(
+    reactions: &BTreeMap<String, HashSet<String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(reactions.iter().map(|(emoji, users)| (emoji, users.len())))
+}
@@ -0,0 +1,29 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// What `create` does when the room already exists.
+#[derive(Debug, Deserialize, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum CreateMode {
+    /// Fails with a conflict.
+    #[default]
+    Create,
+    /// Succeeds if the secret and the settings are the same, so `create` can be retried.
+    Idempotent,
+    /// Applies the settings to the existing room, the secret has to match.
+    Ensure,
+}
+
+impl FromStr for CreateMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "create" => Ok(CreateMode::Create),
+            "idempotent" => Ok(CreateMode::Idempotent),
+            "ensure" => Ok(CreateMode::Ensure),
+            _ => Err(()),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use serde::Serialize;
+
+/// Stable reason of an error, sent along the message by the REST actions and in `error`
+/// responses to WebSocket requests, so clients don't have to match messages.
+#[derive(Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    MissingArgument,
+    InvalidArgument,
+    LimitExceeded,
+    NotFound,
+    MethodNotAllowed,
+    SecretMismatch,
+    /// The admin key is missing or wrong.
+    Unauthorized,
+    Forbidden,
+    RoomNotFound,
+    RoomAlreadyExists,
+    /// `create` in `idempotent` mode found the room with other settings.
+    SettingsConflict,
+    NamespaceFull,
+    RoomFull,
+    InvalidRoomName,
+    /// The room stopped while handling the request.
+    RoomUnavailable,
+    /// The room was destroyed while the client is still connected.
+    RoomDestroyed,
+    MessageNotFound,
+    PollNotFound,
+    AlreadyVoted,
+    EventsUnavailable,
+    SlowMode,
+}
@@ -4,6 +4,10 @@ use serde::{Deserialize, Serialize};
 #[derive(Deserialize, Clone, Serialize)]
 pub struct Message<'a> {
     pub textroom: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(rename = "replyTo", skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<u64>,
     pub room: &'a str,
     pub r#type: &'a str,
     pub from: &'a str,
@@ -16,6 +20,7 @@ impl<'a> Message<'a> {
     pub const MODERATE: &'static str = "moderate";
     pub const ANNOUNCEMENT: &'static str = "announcement";
     pub const MESSAGE: &'static str = "message";
+    pub const POLL: &'static str = "poll";
     pub const TYPE_BAN: &'static str = "ban";
     pub const TYPE_ROOM_CREATED: &'static str = "room_created";
     pub const TYPE_ROOM_DESTROYED: &'static str = "room_destroyed";
@@ -23,8 +28,10 @@ impl<'a> Message<'a> {
     pub fn room_created(room: &'a str) -> Message<'a> {
         Message {
             textroom: Message::MODERATE,
+            id: None,
+            reply_to: None,
             room,
-            r#type: Message::TYPE_ROOM_CREATED.into(),
+            r#type: Message::TYPE_ROOM_CREATED,
             text: "",
             date: Utc::now(),
             from: "",
@@ -34,8 +41,10 @@ impl<'a> Message<'a> {
     pub fn room_destroyed(room: &'a str) -> Message<'a> {
         Message {
             textroom: Message::MODERATE,
+            id: None,
+            reply_to: None,
             room,
-            r#type: Message::TYPE_ROOM_DESTROYED.into(),
+            r#type: Message::TYPE_ROOM_DESTROYED,
             text: "",
             date: Utc::now(),
             from: "",
@@ -1,18 +1,40 @@
+pub use chat_message::ChatMessage;
+pub use create_mode::CreateMode;
+pub use error_code::ErrorCode;
 pub use message::Message;
+pub use namespace::{Namespace, Namespaces};
+pub use overflow_policy::OverflowPolicy;
 pub use params::*;
 pub use participant::Participant;
+pub use pinned_message::PinnedMessage;
+pub use poll::{Poll, PollResults};
 pub use room::Room;
 pub use room_info::RoomInfo;
-pub use text_room_event::TextRoomEvent;
+pub use room_settings::RoomSettings;
+pub use room_update::RoomUpdate;
+pub use slow_consumer_policy::SlowConsumerPolicy;
+pub use text_room_event::{Sequenced, TextRoomEvent, Typer};
 pub use text_room_request::TextRoomRequest;
 pub use text_room_response::TextRoomResponse;
+pub use welcome::{Unread, Welcome};
 
+mod chat_message;
+mod create_mode;
+mod error_code;
 mod message;
+mod namespace;
+mod overflow_policy;
 
 mod params;
 mod participant;
+mod pinned_message;
+mod poll;
 pub mod room;
 mod room_info;
+mod room_settings;
+mod room_update;
+mod slow_consumer_policy;
 mod text_room_event;
 mod text_room_request;
 mod text_room_response;
+mod welcome;
@@ -0,0 +1,128 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::misc::is_same_token;
+use crate::model::Room;
+
+/// Settings shared by the rooms under a uid prefix, e.g. the `/dev` or `/prod` tenant.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Namespace {
+    pub prefix: String,
+    /// Posted to by the rooms created without `post`.
+    pub webhook: Option<String>,
+    /// Webhook posts are signed with each of them, so that a key can be rotated.
+    #[serde(default)]
+    pub signing_keys: Vec<String>,
+    pub max_rooms: Option<usize>,
+    pub max_participants: Option<usize>,
+    /// Does for the rooms of the namespace what the admin key does for every room.
+    pub admin_secret: Option<String>,
+}
+
+/// Namespaces nest, e.g. `/prod/eu` in `/prod`. A room takes each setting from the innermost
+/// namespace that has it, while the room limit and the admin secret of every namespace apply.
+#[derive(Debug, Default)]
+pub struct Namespaces {
+    // outermost first
+    namespaces: Vec<Namespace>,
+}
+
+impl Namespaces {
+    pub fn new(mut namespaces: Vec<Namespace>) -> Result<Namespaces, String> {
+        namespaces.sort_by_key(|e| e.prefix.trim_end_matches('/').len());
+        for (i, namespace) in namespaces.iter().enumerate() {
+            let prefix = namespace.prefix.trim_end_matches('/');
+            if !prefix.is_empty() && !Room::is_valid_uid(prefix) {
+                return Err(format!("namespace `{}` is invalid", namespace.prefix));
+            }
+            if namespaces[..i]
+                .iter()
+                .any(|e| e.prefix.trim_end_matches('/') == prefix)
+            {
+                return Err(format!("namespace `{}` is duplicated", namespace.prefix));
+            }
+        }
+        Ok(Namespaces { namespaces })
+    }
+
+    /// Reads a JSON array of [Namespace].
+    pub fn load(path: &str) -> Result<Namespaces, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let namespaces = serde_json::from_str(&json).map_err(|e| format!("{path}: {e}"))?;
+        Namespaces::new(namespaces)
+    }
+
+    /// The namespaces `uid` is under, outermost first.
+    pub fn of<'a>(&'a self, uid: &'a str) -> impl Iterator<Item = &'a Namespace> {
+        self.namespaces
+            .iter()
+            .filter(move |e| Room::is_under(uid, &e.prefix))
+    }
+
+    /// What applies to the room `uid`, `prefix` being its innermost namespace.
+    pub fn settings(&self, uid: &str) -> Namespace {
+        let mut result = Namespace::default();
+        for namespace in self.of(uid) {
+            result.prefix.clone_from(&namespace.prefix);
+            if namespace.webhook.is_some() {
+                result.webhook.clone_from(&namespace.webhook);
+            }
+            if !namespace.signing_keys.is_empty() {
+                result.signing_keys.clone_from(&namespace.signing_keys);
+            }
+            if namespace.max_rooms.is_some() {
+                result.max_rooms = namespace.max_rooms;
+            }
+            if namespace.max_participants.is_some() {
+                result.max_participants = namespace.max_participants;
+            }
+            if namespace.admin_secret.is_some() {
+                result.admin_secret.clone_from(&namespace.admin_secret);
+            }
+        }
+        result
+    }
+
+    /// Whether `secret` is the admin secret of a namespace of `uid`, which can also be a prefix.
+    pub fn is_admin_secret(&self, uid: &str, secret: &str) -> bool {
+        !secret.is_empty()
+            && self
+                .of(uid)
+                .filter_map(|e| e.admin_secret.as_deref())
+                .any(|e| is_same_token(e, secret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_namespaces() {
+        let json = r#"[
+            {"prefix": "/prod/eu", "maxParticipants": 50, "adminSecret": "eu"},
+            {"prefix": "/prod", "webhook": "https://prod", "maxRooms": 10, "adminSecret": "prod"},
+            {"prefix": "/dev", "signingKeys": ["new", "old"]}
+        ]"#;
+        let namespaces = Namespaces::new(serde_json::from_str(json).unwrap()).unwrap();
+
+        let settings = namespaces.settings("/prod/eu/1");
+        assert_eq!(settings.prefix, "/prod/eu");
+        assert_eq!(settings.webhook.as_deref(), Some("https://prod"));
+        assert_eq!(settings.max_rooms, Some(10));
+        assert_eq!(settings.max_participants, Some(50));
+        assert_eq!(namespaces.settings("/dev/1").signing_keys, ["new", "old"]);
+        assert_eq!(namespaces.settings("/devices/1"), Namespace::default());
+
+        assert!(namespaces.is_admin_secret("/prod/eu/1", "prod"));
+        assert!(namespaces.is_admin_secret("/prod/eu", "eu"));
+        assert!(!namespaces.is_admin_secret("/prod/us", "eu"));
+        assert!(!namespaces.is_admin_secret("", "prod"));
+        assert!(!namespaces.is_admin_secret("/dev/1", ""));
+
+        let duplicated = vec![namespaces.settings("/dev"), namespaces.settings("/dev/")];
+        assert!(Namespaces::new(duplicated).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// What to do with clients joining a room that has `maxParticipants` already.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Answers `join` with `503 room_full` and a `Retry-After` instead of upgrading the connection.
+    #[default]
+    #[serde(rename = "reject")]
+    Reject,
+    /// Lets them in as read-only viewers, who receive the room events but are not participants.
+    #[serde(rename = "viewers")]
+    Viewers,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(OverflowPolicy::Reject),
+            "viewers" => Ok(OverflowPolicy::Viewers),
+            _ => Err(()),
+        }
+    }
+}
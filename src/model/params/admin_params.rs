@@ -0,0 +1,58 @@
+use serde::Deserialize;
+
+use crate::misc::{Params, ParseParamError, QueryParams};
+
+/// A page of the rooms under a uid prefix.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminParams {
+    /// `/dev` covers `/dev` and `/dev/528` but not `/devices`, every room by default.
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl Params for AdminParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(AdminParams {
+            prefix: params.get("prefix").unwrap_or_default(),
+            offset: params.get_parsed("offset")?.unwrap_or(0),
+            limit: params.get_parsed("limit")?,
+        })
+    }
+}
+
+/// The prefix is required, `/` destroys every room.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkDestroyParams {
+    pub prefix: String,
+}
+
+impl Params for BulkDestroyParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(BulkDestroyParams {
+            prefix: params.require("prefix")?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkAnnounceParams {
+    pub prefix: String,
+    pub r#type: String,
+    pub text: String,
+}
+
+impl Params for BulkAnnounceParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(BulkAnnounceParams {
+            prefix: params.require("prefix")?,
+            r#type: params.require("type")?,
+            text: params.require("text")?,
+        })
+    }
+}
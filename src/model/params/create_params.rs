@@ -1,9 +1,26 @@
+use serde::Deserialize;
+
 use crate::misc::{Params, ParseParamError, QueryParams};
+use crate::model::{CreateMode, OverflowPolicy, SlowConsumerPolicy};
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CreateParams {
     pub secret: String,
     pub post: Option<String>,
+    #[serde(default)]
     pub post_types: Vec<String>,
+    pub slow_consumer: Option<SlowConsumerPolicy>,
+    pub slow_mode: Option<u64>,
+    /// Seconds before the room is destroyed automatically, 0 for never.
+    pub ttl: Option<u64>,
+    /// Seconds without participants before the room is destroyed automatically, 0 for never.
+    pub idle_timeout: Option<u64>,
+    pub max_participants: Option<usize>,
+    pub overflow: Option<OverflowPolicy>,
+    pub presence_threshold: Option<usize>,
+    #[serde(default)]
+    pub mode: CreateMode,
 }
 
 impl Params for CreateParams {
@@ -12,6 +29,14 @@ impl Params for CreateParams {
             secret: params.require("secret")?,
             post: params.get("post"),
             post_types: params.get_list("postTypes"),
+            slow_consumer: params.get_parsed("slowConsumer")?,
+            slow_mode: params.get_parsed("slowMode")?,
+            ttl: params.get_parsed("ttl")?,
+            idle_timeout: params.get_parsed("idleTimeout")?,
+            max_participants: params.get_parsed("maxParticipants")?,
+            overflow: params.get_parsed("overflow")?,
+            presence_threshold: params.get_parsed("presenceThreshold")?,
+            mode: params.get_parsed("mode")?.unwrap_or_default(),
         })
     }
 }
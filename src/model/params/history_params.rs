@@ -0,0 +1,17 @@
+use serde::Deserialize;
+
+use crate::misc::{Params, ParseParamError, QueryParams};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryParams {
+    pub limit: Option<usize>,
+}
+
+impl Params for HistoryParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(HistoryParams {
+            limit: params.get_parsed("limit")?,
+        })
+    }
+}
@@ -1,10 +1,17 @@
+use serde::Deserialize;
+
 use crate::misc::{Params, ParseParamError, QueryParams};
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct JoinParams {
     pub username: Option<String>,
     pub display: Option<String>,
     pub image_url: Option<String>,
+    /// Token from a previous `welcome`, to resume that session.
+    pub resume: Option<String>,
+    /// `seq` of the last event received before the connection dropped.
+    pub last: Option<u64>,
 }
 
 impl Params for JoinParams {
@@ -13,6 +20,8 @@ impl Params for JoinParams {
             username: params.get("username"),
             display: params.get("display"),
             image_url: params.get("imageUrl"),
+            resume: params.get("resume"),
+            last: params.get_parsed("last")?,
         })
     }
 }
@@ -21,14 +30,14 @@ impl Params for JoinParams {
 mod tests {
     use hyper::Uri;
 
-    use crate::misc::Params;
+    use crate::misc::{Params, QueryParams};
     use crate::model::JoinParams;
 
     #[test]
     fn it_works() {
         let url = "ws://10.0.2.2:9339/dev/528/join?username=133&display=TH%E1%BB%AC%20NGHI%E1%BB%86M&imageUrl=https%3A%2F%2Fdev.shoplive.vn%2Fcontent%2Fimages%2Favatars%2F133.jpg";
         let uri = url.parse::<Uri>().unwrap();
-        let params = JoinParams::parse_uri(&uri).unwrap();
+        let params = JoinParams::parse(&QueryParams::parse(uri.query())).unwrap();
         println!("{:?}", params);
         assert_eq!(params.display, Some("THỬ NGHIỆM".to_string()));
         assert_eq!(
@@ -1,7 +1,11 @@
+use serde::Deserialize;
+
 use crate::misc::{Params, ParseParamError, QueryParams};
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct LastAnnouncementParams {
+    #[serde(default)]
     pub types: Vec<String>,
 }
 
@@ -1,11 +1,27 @@
+pub use admin_params::{AdminParams, BulkAnnounceParams, BulkDestroyParams};
 pub use create_params::CreateParams;
 pub use destroy_params::DestroyParams;
+pub use history_params::HistoryParams;
 pub use join_params::JoinParams;
 pub use last_announcement_params::LastAnnouncementParams;
+pub use participants_params::ParticipantsParams;
 pub use photo_params::PhotoParams;
+pub use pin_params::PinParams;
+pub use poll_params::{ClosePollParams, PollParams};
+pub use read_params::ReadParams;
+pub use thread_params::ThreadParams;
+pub use update_params::{RotateSecretParams, UpdateParams};
 
+mod admin_params;
 mod create_params;
 mod destroy_params;
+mod history_params;
 mod join_params;
 mod last_announcement_params;
+mod participants_params;
 mod photo_params;
+mod pin_params;
+mod poll_params;
+mod read_params;
+mod thread_params;
+mod update_params;
@@ -0,0 +1,24 @@
+use serde::Deserialize;
+
+use crate::misc::{Params, ParseParamError, QueryParams};
+
+/// A page of the participants of a room, every one of them without `limit`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParticipantsParams {
+    /// Part of the username or display name, ignoring case.
+    pub search: Option<String>,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl Params for ParticipantsParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(ParticipantsParams {
+            search: params.get("search"),
+            offset: params.get_parsed("offset")?.unwrap_or(0),
+            limit: params.get_parsed("limit")?,
+        })
+    }
+}
@@ -1,5 +1,9 @@
+use serde::Deserialize;
+
 use crate::misc::{Params, ParseParamError, QueryParams};
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PhotoParams {
     pub username: String,
 }
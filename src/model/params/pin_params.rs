@@ -0,0 +1,24 @@
+use serde::Deserialize;
+
+use crate::misc::{Params, ParseParamError, QueryParams};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinParams {
+    pub secret: String,
+    pub id: u64,
+    /// Seconds before the message is unpinned automatically.
+    pub expires_in: Option<u64>,
+}
+
+impl Params for PinParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(PinParams {
+            secret: params.require("secret")?,
+            id: params
+                .get_parsed("id")?
+                .ok_or(ParseParamError::FieldRequired { name: "id" })?,
+            expires_in: params.get_parsed("expiresIn")?,
+        })
+    }
+}
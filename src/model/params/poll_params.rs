@@ -0,0 +1,55 @@
+use serde::Deserialize;
+
+use crate::misc::{Params, ParseParamError, QueryParams};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PollParams {
+    pub secret: String,
+    pub question: String,
+    #[serde(default)]
+    pub options: Vec<String>,
+    /// Seconds before the poll closes.
+    pub duration: Option<u64>,
+    #[serde(default)]
+    pub multiple: bool,
+    /// Correct option indexes, turns the poll into a quiz.
+    #[serde(default)]
+    pub answers: Vec<usize>,
+}
+
+impl Params for PollParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(PollParams {
+            secret: params.require("secret")?,
+            question: params.require("question")?,
+            options: params.get_list("options"),
+            duration: params.get_parsed("duration")?,
+            multiple: params.get_parsed("multiple")?.unwrap_or(false),
+            answers: params
+                .get_list("answers")
+                .iter()
+                .map(|e| e.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| ParseParamError::InvalidValue { name: "answers" })?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClosePollParams {
+    pub secret: String,
+    pub id: u64,
+}
+
+impl Params for ClosePollParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(ClosePollParams {
+            secret: params.require("secret")?,
+            id: params
+                .get_parsed("id")?
+                .ok_or(ParseParamError::FieldRequired { name: "id" })?,
+        })
+    }
+}
@@ -0,0 +1,17 @@
+use serde::Deserialize;
+
+use crate::misc::{Params, ParseParamError, QueryParams};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadParams {
+    pub username: Option<String>,
+}
+
+impl Params for ReadParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(ReadParams {
+            username: params.get("username"),
+        })
+    }
+}
@@ -0,0 +1,19 @@
+use serde::Deserialize;
+
+use crate::misc::{Params, ParseParamError, QueryParams};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadParams {
+    pub id: u64,
+}
+
+impl Params for ThreadParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(ThreadParams {
+            id: params
+                .get_parsed("id")?
+                .ok_or(ParseParamError::FieldRequired { name: "id" })?,
+        })
+    }
+}
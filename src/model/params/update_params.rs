@@ -0,0 +1,59 @@
+use serde::Deserialize;
+
+use crate::misc::{Params, ParseParamError, QueryParams};
+use crate::model::{OverflowPolicy, SlowConsumerPolicy};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateParams {
+    pub secret: String,
+    /// An empty url stops posting to the webhook.
+    pub post: Option<String>,
+    pub post_types: Option<Vec<String>>,
+    pub slow_consumer: Option<SlowConsumerPolicy>,
+    pub slow_mode: Option<u64>,
+    /// 0 removes the expiry.
+    pub ttl: Option<u64>,
+    /// 0 removes the timeout.
+    pub idle_timeout: Option<u64>,
+    /// 0 removes the limit.
+    pub max_participants: Option<usize>,
+    pub overflow: Option<OverflowPolicy>,
+    /// 0 restores the default threshold.
+    pub presence_threshold: Option<usize>,
+}
+
+impl Params for UpdateParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(UpdateParams {
+            secret: params.require("secret")?,
+            post: params.get("post"),
+            post_types: params
+                .get("postTypes")
+                .map(|_| params.get_list("postTypes")),
+            slow_consumer: params.get_parsed("slowConsumer")?,
+            slow_mode: params.get_parsed("slowMode")?,
+            ttl: params.get_parsed("ttl")?,
+            idle_timeout: params.get_parsed("idleTimeout")?,
+            max_participants: params.get_parsed("maxParticipants")?,
+            overflow: params.get_parsed("overflow")?,
+            presence_threshold: params.get_parsed("presenceThreshold")?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateSecretParams {
+    pub secret: String,
+    pub new_secret: String,
+}
+
+impl Params for RotateSecretParams {
+    fn parse<'a>(params: &QueryParams) -> Result<Self, ParseParamError<'a>> {
+        Ok(RotateSecretParams {
+            secret: params.require("secret")?,
+            new_secret: params.require("newSecret")?,
+        })
+    }
+}
@@ -0,0 +1,18 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::model::ChatMessage;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PinnedMessage {
+    #[serde(flatten)]
+    pub message: ChatMessage,
+    #[serde(rename = "pinnedAt", with = "crate::misc::date_serde")]
+    pub pinned_at: DateTime<Utc>,
+    #[serde(
+        rename = "expiresAt",
+        with = "crate::misc::date_serde::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Poll {
+    pub id: u64,
+    pub question: String,
+    pub options: Vec<String>,
+    pub multiple: bool,
+    /// Number of votes by option index.
+    pub votes: Vec<usize>,
+    /// Options picked by username, only the number of voters is exposed.
+    #[serde(rename = "voters", serialize_with = "serialize_len")]
+    pub ballots: HashMap<String, Vec<usize>>,
+    #[serde(rename = "closesAt", with = "crate::misc::date_serde")]
+    pub closes_at: DateTime<Utc>,
+    pub closed: bool,
+    /// Correct options of a quiz, kept secret until the poll is closed.
+    #[serde(skip)]
+    pub answers: Vec<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correct: Option<Vec<usize>>,
+    /// Users who picked exactly the correct options of a closed quiz.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub winners: Option<Vec<String>>,
+    /// Votes were received since the last `tally` event.
+    #[serde(skip)]
+    pub is_dirty: bool,
+}
+
+/// Final results posted to the room webhook.
+#[derive(Serialize)]
+pub struct PollResults<'a> {
+    pub textroom: &'static str,
+    pub room: &'a str,
+    #[serde(flatten)]
+    pub poll: &'a Poll,
+}
+
+fn serialize_len<S>(ballots: &HashMap<String, Vec<usize>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u64(ballots.len() as u64)
+}
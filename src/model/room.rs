@@ -1,14 +1,140 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ROOM_UID_MAX_LEN;
 use crate::misc::StringExt;
+use crate::model::{OverflowPolicy, SlowConsumerPolicy};
 
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Room {
     pub uid: String,
     pub secret: String,
     pub post: Option<String>,
     pub post_types: Vec<String>,
+    pub slow_consumer: SlowConsumerPolicy,
+    /// Seconds between two messages of the same client, 0 when off.
+    #[serde(default)]
+    pub slow_mode: u64,
+    #[serde(
+        default,
+        with = "crate::misc::date_serde::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Seconds without participants before the room is destroyed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout: Option<u64>,
+    /// The limit of the namespace applies too, whichever is lower.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_participants: Option<usize>,
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+    /// Participants from which joins and leaves are batched in `presence` events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_threshold: Option<usize>,
 }
 
 impl Room {
+    /// A room with default settings.
+    #[cfg(test)]
+    pub fn new(uid: &str, secret: &str) -> Room {
+        Room {
+            uid: uid.to_string(),
+            secret: secret.to_string(),
+            post: None,
+            post_types: Vec::new(),
+            slow_consumer: Default::default(),
+            slow_mode: 0,
+            expires_at: None,
+            idle_timeout: None,
+            max_participants: None,
+            overflow: Default::default(),
+            presence_threshold: None,
+        }
+    }
+
     pub fn name(&self) -> &str {
         self.uid.as_str().substring_after_last('/')
     }
+
+    /// Names of the settings of `other` that differ from this room's, as `create` params.
+    ///
+    /// The expiry is left out, a retried `create` computes a later one from the same `ttl`.
+    pub fn differences(&self, other: &Room) -> Vec<&'static str> {
+        let mut result = Vec::new();
+        if self.post != other.post {
+            result.push("post");
+        }
+        if self.post_types != other.post_types {
+            result.push("postTypes");
+        }
+        if self.slow_consumer != other.slow_consumer {
+            result.push("slowConsumer");
+        }
+        if self.slow_mode != other.slow_mode {
+            result.push("slowMode");
+        }
+        if self.idle_timeout != other.idle_timeout {
+            result.push("idleTimeout");
+        }
+        if self.max_participants != other.max_participants {
+            result.push("maxParticipants");
+        }
+        if self.overflow != other.overflow {
+            result.push("overflow");
+        }
+        if self.presence_threshold != other.presence_threshold {
+            result.push("presenceThreshold");
+        }
+        result
+    }
+
+    /// `/dev` covers `/dev` and `/dev/528` but not `/devices`, an empty prefix or `/` covers every
+    /// uid.
+    pub fn is_under(uid: &str, prefix: &str) -> bool {
+        let prefix = prefix.trim_end_matches('/');
+        prefix.is_empty()
+            || uid
+                .strip_prefix(prefix)
+                .is_some_and(|e| e.is_empty() || e.starts_with('/'))
+    }
+
+    /// `/path/to/name`, without empty segments, whitespace or control characters.
+    pub fn is_valid_uid(uid: &str) -> bool {
+        uid.len() <= ROOM_UID_MAX_LEN
+            && uid.starts_with('/')
+            && uid[1..].split('/').all(|e| !e.is_empty())
+            && !uid.chars().any(|e| e.is_whitespace() || e.is_control())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_uid() {
+        assert!(Room::is_valid_uid("/app/room"));
+        assert!(Room::is_valid_uid("/status"));
+        assert!(!Room::is_valid_uid("app/room"));
+        assert!(!Room::is_valid_uid("/app//room"));
+        assert!(!Room::is_valid_uid("/app/room/"));
+        assert!(!Room::is_valid_uid("/app/my room"));
+        assert!(!Room::is_valid_uid(&format!(
+            "/{}",
+            "a".repeat(ROOM_UID_MAX_LEN)
+        )));
+    }
+
+    #[test]
+    fn test_under_prefix() {
+        assert!(Room::is_under("/dev/528", "/dev"));
+        assert!(Room::is_under("/dev/528", "/dev/"));
+        assert!(Room::is_under("/dev", "/dev"));
+        assert!(Room::is_under("/dev/528", "/"));
+        assert!(Room::is_under("/dev/528", ""));
+        assert!(!Room::is_under("/devices/1", "/dev"));
+        assert!(!Room::is_under("/prod/528", "/dev"));
+    }
 }
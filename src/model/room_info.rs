@@ -6,5 +6,12 @@ use crate::model::Participant;
 pub struct RoomInfo {
     pub room: String,
     pub participants: Vec<Participant>,
+    /// Overflow clients of this instance, not listed in `participants`.
+    pub viewers: usize,
     pub messages: usize,
+    /// Events dropped for slow clients.
+    pub dropped: u64,
+    /// Clients disconnected for being too slow.
+    #[serde(rename = "slowConsumers")]
+    pub slow_consumers: u64,
 }
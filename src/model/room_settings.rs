@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+/// Room behaviour a client may need to adapt its UI.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct RoomSettings {
+    /// Seconds during which further `typing` requests are ignored.
+    #[serde(rename = "typingThrottle")]
+    pub typing_throttle: u64,
+    /// Whether `read` events are currently broadcast.
+    #[serde(rename = "readReceipts")]
+    pub read_receipts: bool,
+    #[serde(rename = "historySize")]
+    pub history_size: usize,
+    /// Seconds a client has to wait between two messages, 0 when off.
+    #[serde(rename = "slowMode")]
+    pub slow_mode: u64,
+    /// Participants from which `presence` events replace `join` and `leave`.
+    #[serde(rename = "presenceThreshold")]
+    pub presence_threshold: usize,
+}
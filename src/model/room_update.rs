@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{OverflowPolicy, SlowConsumerPolicy};
+
+/// Changes to a live room, the settings left to `None` are kept.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// An empty url stops posting to the webhook.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_types: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow_consumer: Option<SlowConsumerPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow_mode: Option<u64>,
+    /// `Some(None)` removes the expiry.
+    #[serde(
+        default,
+        with = "crate::misc::date_serde::nullable",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    /// 0 removes the timeout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout: Option<u64>,
+    /// 0 removes the limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_participants: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overflow: Option<OverflowPolicy>,
+    /// 0 restores the default threshold.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_threshold: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removed_expiry() {
+        let update: RoomUpdate = serde_json::from_str(r#"{"expiresAt": null}"#).unwrap();
+        assert_eq!(update.expires_at, Some(None));
+        let json = serde_json::to_string(&update).unwrap();
+        assert_eq!(json, r#"{"expiresAt":null}"#);
+        let update: RoomUpdate = serde_json::from_str("{}").unwrap();
+        assert_eq!(update.expires_at, None);
+        assert_eq!(serde_json::to_string(&update).unwrap(), "{}");
+    }
+}
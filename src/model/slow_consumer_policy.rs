@@ -0,0 +1,27 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// What to do when a client does not read its events fast enough.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, Eq, PartialEq)]
+pub enum SlowConsumerPolicy {
+    /// Drops the oldest events that can be recovered with a replay.
+    #[default]
+    #[serde(rename = "drop")]
+    DropOldest,
+    /// Closes the connection, the client may resume its session.
+    #[serde(rename = "disconnect")]
+    Disconnect,
+}
+
+impl FromStr for SlowConsumerPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(SlowConsumerPolicy::DropOldest),
+            "disconnect" => Ok(SlowConsumerPolicy::Disconnect),
+            _ => Err(()),
+        }
+    }
+}
@@ -1,6 +1,10 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::model::{Participant, RoomSettings};
+
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(tag = "textroom")]
 pub enum TextRoomEvent<'a> {
@@ -32,13 +36,146 @@ pub enum TextRoomEvent<'a> {
         participants: usize,
     },
 
+    /// Replaces `join` and `leave` in large rooms, coalesced once per room tick.
+    #[serde(rename = "presence")]
+    Presence {
+        joined: Vec<Participant>,
+        left: Vec<Participant>,
+        /// Number of joins and leaves not listed.
+        others: usize,
+        participants: usize,
+    },
+
     #[serde(rename = "message")]
     Message {
+        id: u64,
+        from: &'a str,
+        display: &'a str,
+        #[serde(with = "crate::misc::date_serde")]
+        date: DateTime<Utc>,
+        text: &'a str,
+        r#type: &'a str,
+        #[serde(rename = "replyTo", default, skip_serializing_if = "Option::is_none")]
+        reply_to: Option<u64>,
+    },
+
+    #[serde(rename = "reaction")]
+    Reaction {
+        id: u64,
+        emoji: &'a str,
+        from: &'a str,
+        /// Number of users who currently reacted with this emoji.
+        count: usize,
+    },
+
+    #[serde(rename = "pinned")]
+    Pinned {
+        id: u64,
         from: &'a str,
         display: &'a str,
         #[serde(with = "crate::misc::date_serde")]
         date: DateTime<Utc>,
         text: &'a str,
         r#type: &'a str,
+        #[serde(
+            rename = "expiresAt",
+            default,
+            with = "crate::misc::date_serde::option",
+            skip_serializing_if = "Option::is_none"
+        )]
+        expires_at: Option<DateTime<Utc>>,
     },
+
+    #[serde(rename = "unpinned")]
+    Unpinned { id: u64 },
+
+    #[serde(rename = "poll")]
+    Poll {
+        id: u64,
+        question: &'a str,
+        #[serde(borrow)]
+        options: Vec<&'a str>,
+        multiple: bool,
+        #[serde(rename = "closesAt", with = "crate::misc::date_serde")]
+        closes_at: DateTime<Utc>,
+    },
+
+    /// Sent at most once per room tick while votes come in.
+    #[serde(rename = "tally")]
+    Tally {
+        poll: u64,
+        votes: Vec<usize>,
+        voters: usize,
+    },
+
+    #[serde(rename = "results")]
+    Results {
+        poll: u64,
+        votes: Vec<usize>,
+        voters: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        correct: Option<Vec<usize>>,
+    },
+
+    /// Only broadcast in small rooms.
+    #[serde(rename = "read")]
+    Read { username: &'a str, id: u64 },
+
+    /// Sent when the room is updated, with the settings now in effect.
+    #[serde(rename = "settings_changed")]
+    SettingsChanged { settings: RoomSettings },
+
+    /// Taps received since the previous tick, by emoji, for at most `BURST_MAX_KINDS` emojis.
+    #[serde(rename = "bursts")]
+    Bursts {
+        #[serde(borrow)]
+        counts: BTreeMap<&'a str, usize>,
+    },
+
+    /// Coalesced once per room tick, never stored nor posted. Like replies, it carries no `seq`:
+    /// it is ephemeral, so it is neither numbered nor replayed after a resume.
+    #[serde(rename = "typing")]
+    Typing {
+        #[serde(borrow)]
+        users: Vec<Typer<'a>>,
+        /// Number of typing users not listed in `users`.
+        others: usize,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Typer<'a> {
+    pub username: &'a str,
+    pub display: &'a str,
+}
+
+impl TextRoomEvent<'_> {
+    /// Critical events are never dropped for slow clients.
+    pub fn is_critical(&self) -> bool {
+        matches!(self, TextRoomEvent::Banned | TextRoomEvent::Destroyed)
+    }
+}
+
+/// An event sent to the whole room, numbered so clients can detect missed ones.
+#[derive(Serialize)]
+pub struct Sequenced<'a, T> {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub event: &'a T,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Sequenced, TextRoomEvent};
+
+    #[test]
+    fn test_sequenced() {
+        let event = TextRoomEvent::Unpinned { id: 3 };
+        let json = serde_json::to_string(&Sequenced {
+            seq: 12,
+            event: &event,
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"seq":12,"textroom":"unpinned","id":3}"#);
+    }
 }
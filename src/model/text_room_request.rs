@@ -30,6 +30,99 @@ pub enum TextRoomRequest {
     Message {
         r#type: String,
         text: String,
+        #[serde(rename = "replyTo", default, skip_serializing_if = "Option::is_none")]
+        reply_to: Option<u64>,
+        #[serde(skip_serializing)]
+        transaction: Option<String>,
+    },
+
+    #[serde(rename = "typing")]
+    Typing {
+        #[serde(skip_serializing)]
+        transaction: Option<String>,
+    },
+
+    #[serde(rename = "react")]
+    React {
+        id: u64,
+        emoji: String,
+        #[serde(default)]
+        remove: bool,
+        #[serde(skip_serializing)]
+        transaction: Option<String>,
+    },
+
+    #[serde(rename = "read")]
+    Read {
+        id: u64,
+        #[serde(skip_serializing)]
+        transaction: Option<String>,
+    },
+
+    #[serde(rename = "pin")]
+    Pin {
+        id: u64,
+        secret: String,
+        /// Seconds before the message is unpinned automatically.
+        #[serde(rename = "expiresIn")]
+        expires_in: Option<u64>,
+        #[serde(skip_serializing)]
+        transaction: Option<String>,
+    },
+
+    #[serde(rename = "unpin")]
+    Unpin {
+        id: u64,
+        secret: String,
+        #[serde(skip_serializing)]
+        transaction: Option<String>,
+    },
+
+    #[serde(rename = "poll")]
+    Poll {
+        secret: String,
+        question: String,
+        options: Vec<String>,
+        /// Seconds before the poll closes.
+        duration: Option<u64>,
+        #[serde(default)]
+        multiple: bool,
+        /// Correct option indexes, turns the poll into a quiz.
+        #[serde(default)]
+        answers: Vec<usize>,
+        #[serde(skip_serializing)]
+        transaction: Option<String>,
+    },
+
+    #[serde(rename = "closePoll")]
+    ClosePoll {
+        id: u64,
+        secret: String,
+        #[serde(skip_serializing)]
+        transaction: Option<String>,
+    },
+
+    #[serde(rename = "vote")]
+    Vote {
+        poll: u64,
+        options: Vec<usize>,
+        #[serde(skip_serializing)]
+        transaction: Option<String>,
+    },
+
+    /// Asks again for the room events numbered after `since`, answered with `events_unavailable`
+    /// when some are no longer kept or too many are missing.
+    #[serde(rename = "replay")]
+    Replay {
+        since: u64,
+        #[serde(skip_serializing)]
+        transaction: Option<String>,
+    },
+
+    #[serde(rename = "burst")]
+    Burst {
+        emoji: String,
+        count: Option<usize>,
         #[serde(skip_serializing)]
         transaction: Option<String>,
     },
@@ -42,6 +135,16 @@ impl TextRoomRequest {
             TextRoomRequest::Ban { transaction, .. } => transaction,
             TextRoomRequest::Leave { transaction, .. } => transaction,
             TextRoomRequest::Message { transaction, .. } => transaction,
+            TextRoomRequest::Typing { transaction } => transaction,
+            TextRoomRequest::React { transaction, .. } => transaction,
+            TextRoomRequest::Burst { transaction, .. } => transaction,
+            TextRoomRequest::Read { transaction, .. } => transaction,
+            TextRoomRequest::Pin { transaction, .. } => transaction,
+            TextRoomRequest::Unpin { transaction, .. } => transaction,
+            TextRoomRequest::Poll { transaction, .. } => transaction,
+            TextRoomRequest::ClosePoll { transaction, .. } => transaction,
+            TextRoomRequest::Vote { transaction, .. } => transaction,
+            TextRoomRequest::Replay { transaction, .. } => transaction,
         }
     }
 }
@@ -61,7 +164,7 @@ mod tests {
         let json = serde_json::to_string(&obj).unwrap();
         let result = r#"{"textroom":"announcement","type":"message","text":"Hello there!","secret":"1q2w3e!@#$"}"#;
         println!("json: {json}");
-        let parsed = serde_json::from_str::<TextRoomRequest>(&result).unwrap();
+        let parsed = serde_json::from_str::<TextRoomRequest>(result).unwrap();
         println!("obj: {:?}", parsed);
         assert_eq!(json, result);
         assert_eq!(obj, parsed);
@@ -81,4 +184,59 @@ mod tests {
         println!("obj: {:?}", parsed);
         assert_eq!(obj, parsed);
     }
+
+    #[test]
+    fn test_typing() {
+        let parsed = serde_json::from_str::<TextRoomRequest>(r#"{"textroom":"typing"}"#).unwrap();
+        assert_eq!(parsed, TextRoomRequest::Typing { transaction: None });
+    }
+
+    #[test]
+    fn test_reply() {
+        let raw = r#"{"textroom":"message","type":"message","text":"How much?","replyTo":3}"#;
+        let parsed = serde_json::from_str::<TextRoomRequest>(raw).unwrap();
+        assert_eq!(
+            parsed,
+            TextRoomRequest::Message {
+                r#type: "message".to_string(),
+                text: "How much?".to_string(),
+                reply_to: Some(3),
+                transaction: None,
+            }
+        );
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_poll() {
+        let raw = r#"{"textroom":"poll","secret":"s","question":"Size?","options":["S","M"]}"#;
+        let parsed = serde_json::from_str::<TextRoomRequest>(raw).unwrap();
+        assert_eq!(
+            parsed,
+            TextRoomRequest::Poll {
+                secret: "s".to_string(),
+                question: "Size?".to_string(),
+                options: vec!["S".to_string(), "M".to_string()],
+                duration: None,
+                multiple: false,
+                answers: vec![],
+                transaction: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_react() {
+        let raw = r#"{"textroom":"react","id":12,"emoji":"👍","transaction":"t1"}"#;
+        let parsed = serde_json::from_str::<TextRoomRequest>(raw).unwrap();
+        assert_eq!(
+            parsed,
+            TextRoomRequest::React {
+                id: 12,
+                emoji: "👍".to_string(),
+                remove: false,
+                transaction: Some("t1".to_string()),
+            }
+        );
+    }
 }
@@ -1,5 +1,7 @@
 use serde::Serialize;
 
+use crate::model::ErrorCode;
+
 #[derive(Serialize, Debug)]
 #[serde(untagged)]
 pub enum TextRoomResponse {
@@ -10,6 +12,7 @@ pub enum TextRoomResponse {
     Error {
         transaction: Option<String>,
         error: String,
+        code: ErrorCode,
     },
 }
 
@@ -20,17 +23,57 @@ impl TextRoomResponse {
             ok: "left".to_string(),
         }
     }
-    pub fn destroyed(transaction: Option<String>) -> TextRoomResponse {
+
+    pub fn error(transaction: Option<String>, code: ErrorCode, error: String) -> TextRoomResponse {
         TextRoomResponse::Error {
             transaction,
-            error: "Room was destroyed".to_string(),
+            error,
+            code,
         }
     }
 
-    pub fn secret(transaction: Option<String>) -> TextRoomResponse {
-        TextRoomResponse::Error {
+    pub fn destroyed(transaction: Option<String>) -> TextRoomResponse {
+        Self::error(
             transaction,
-            error: "Secret does not match.".to_string(),
-        }
+            ErrorCode::RoomDestroyed,
+            "Room was destroyed".to_string(),
+        )
+    }
+
+    pub fn already_voted(transaction: Option<String>) -> TextRoomResponse {
+        Self::error(
+            transaction,
+            ErrorCode::AlreadyVoted,
+            "Already voted.".to_string(),
+        )
+    }
+
+    pub fn events_unavailable(transaction: Option<String>) -> TextRoomResponse {
+        Self::error(
+            transaction,
+            ErrorCode::EventsUnavailable,
+            "Some events are no longer available, join again.".to_string(),
+        )
+    }
+
+    pub fn slow_mode(transaction: Option<String>) -> TextRoomResponse {
+        Self::error(
+            transaction,
+            ErrorCode::SlowMode,
+            "Slow mode is on, wait before sending another message.".to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code() {
+        let response = TextRoomResponse::slow_mode(Some("1".to_string()));
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["transaction"], "1");
+        assert_eq!(json["code"], "slow_mode");
     }
 }
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::model::{Participant, PinnedMessage, Poll, RoomSettings};
+
+/// First event sent to a client right after joining, a snapshot of the room.
+#[derive(Serialize)]
+#[serde(tag = "textroom", rename = "welcome")]
+pub struct Welcome<'a> {
+    pub id: usize,
+    /// Pass it as `resume` when reconnecting to keep the same session.
+    pub resume: &'a str,
+    /// Whether a previous session was restored, in which case missed events follow.
+    pub resumed: bool,
+    /// `seq` of the last room event, the next one will be `seq + 1`. Replies and ephemeral events
+    /// such as `typing` have no `seq`.
+    pub seq: u64,
+    /// Some missed events were not replayed, they are no longer kept or were too many.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub gap: bool,
+    /// Number of participants, including the new one unless it is a viewer.
+    pub count: usize,
+    /// The room was full, the client can only read.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub viewer: bool,
+    /// Omitted for rooms too big to be listed, use the `participants` action instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub participants: Option<Vec<&'a Participant>>,
+    /// Last announcement text by type.
+    pub announcements: &'a HashMap<String, String>,
+    pub pinned: &'a [PinnedMessage],
+    /// Polls still open.
+    pub polls: Vec<&'a Poll>,
+    /// Present when the user has read markers in this room.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unread: Option<Unread>,
+    pub settings: RoomSettings,
+}
+
+#[derive(Serialize)]
+pub struct Unread {
+    #[serde(rename = "lastRead")]
+    pub last_read: u64,
+    /// Messages after `lastRead`, counted in the history so at most its size.
+    pub count: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::model::{RoomSettings, Welcome};
+
+    #[test]
+    fn it_works() {
+        let announcements = HashMap::new();
+        let welcome = Welcome {
+            id: 7,
+            resume: "token",
+            resumed: false,
+            seq: 42,
+            gap: false,
+            count: 1,
+            viewer: false,
+            participants: None,
+            announcements: &announcements,
+            pinned: &[],
+            polls: vec![],
+            unread: None,
+            settings: RoomSettings {
+                typing_throttle: 3,
+                read_receipts: true,
+                history_size: 200,
+                slow_mode: 0,
+                presence_threshold: 200,
+            },
+        };
+        let json = serde_json::to_string(&welcome).unwrap();
+        assert_eq!(
+            json,
+            r#"{"textroom":"welcome","id":7,"resume":"token","resumed":false,"seq":42,"count":1,"announcements":{},"pinned":[],"polls":[],"settings":{"typingThrottle":3,"readReceipts":true,"historySize":200,"slowMode":0,"presenceThreshold":200}}"#
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use tokio::sync::mpsc;
+
+use crate::log;
+use crate::misc::random_token;
+use crate::model::{Participant, Room, RoomUpdate};
+
+/// Channel where instances tell each other about created and destroyed rooms.
+pub const ROOMS_CHANNEL: &str = "rooms";
+
+pub fn room_channel(uid: &str) -> String {
+    format!("room:{uid}")
+}
+
+/// Pub/sub shared by every instance of the server, so that a room can have clients on several
+/// of them.
+pub trait Backplane: Send + Sync {
+    /// Publishes without waiting, delivery is best effort.
+    fn publish(&self, channel: &str, payload: String);
+
+    /// Everything published on `channel` from now on, including by this instance.
+    fn subscribe(&self, channel: &str) -> mpsc::UnboundedReceiver<String>;
+}
+
+/// Low bits of a replicated message id, telling which instance assigned it. Ids stay under 2^53
+/// so that JavaScript clients read them exactly.
+pub const INSTANCE_TAG_BITS: u32 = 20;
+
+/// A [Backplane] and the id of this instance, used to ignore our own publications.
+#[derive(Clone)]
+pub struct Replication {
+    backplane: Arc<dyn Backplane>,
+    origin: Arc<str>,
+    tag: u64,
+}
+
+impl Replication {
+    pub fn new(backplane: Arc<dyn Backplane>) -> Replication {
+        Replication {
+            backplane,
+            origin: random_token().into(),
+            tag: rand::thread_rng().gen_range(0..1 << INSTANCE_TAG_BITS),
+        }
+    }
+
+    /// Id of this instance.
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// The message id following `last`, whichever instance assigned `last`. Instances draw their
+    /// tag at random, so two of them only assign the same id if they drew the same tag.
+    pub fn message_id(&self, last: u64) -> u64 {
+        (((last >> INSTANCE_TAG_BITS) + 1) << INSTANCE_TAG_BITS) | self.tag
+    }
+
+    pub fn publish<T: Serialize>(&self, channel: &str, sync: &T) {
+        let envelope = Envelope {
+            origin: self.origin.to_string(),
+            sync,
+        };
+        self.backplane
+            .publish(channel, serde_json::to_string(&envelope).unwrap());
+    }
+
+    pub fn subscribe<T: DeserializeOwned>(&self, channel: &str) -> Subscription<T> {
+        Subscription {
+            rx: self.backplane.subscribe(channel),
+            origin: self.origin.clone(),
+            _sync: PhantomData,
+        }
+    }
+}
+
+/// What other instances published on a channel.
+pub struct Subscription<T> {
+    rx: mpsc::UnboundedReceiver<String>,
+    origin: Arc<str>,
+    _sync: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Subscription<T> {
+    /// `None` once the backplane is gone.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            let payload = self.rx.recv().await?;
+            match serde_json::from_str::<Envelope<T>>(&payload) {
+                Ok(envelope) if *envelope.origin != *self.origin => return Some(envelope.sync),
+                Ok(_) => {}
+                Err(error) => {
+                    log!("invalid sync message: {error}");
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    origin: String,
+    #[serde(flatten)]
+    sync: T,
+}
+
+/// Published on [ROOMS_CHANNEL].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "sync", rename_all = "camelCase")]
+pub enum RegistrySync {
+    Created {
+        room: Room,
+    },
+    Destroyed {
+        room: String,
+    },
+    /// Sent by a starting instance, others answer with the rooms they created.
+    Hello,
+}
+
+/// Published on the channel of a room.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "sync", rename_all = "camelCase")]
+pub enum RoomSync {
+    /// A room event to send to the clients of every instance.
+    Event {
+        event: serde_json::Value,
+    },
+    /// The participants connected to an instance, replacing the ones it sent before.
+    Presence {
+        instance: String,
+        participants: Vec<Participant>,
+    },
+    /// Who joined and left an instance since it last published its presence.
+    PresenceChanged {
+        instance: String,
+        joined: Vec<Participant>,
+        left: Vec<Participant>,
+    },
+    Ban {
+        username: String,
+    },
+    Updated {
+        update: RoomUpdate,
+    },
+    /// Sent by the instance that created the room once per
+    /// [PRESENCE_REFRESH](crate::config::PRESENCE_REFRESH), the replicas expire when it stops.
+    Refresh,
+}
+
+/// Backplane of instances living in the same process.
+#[cfg(test)]
+#[derive(Default)]
+pub struct InProcessBackplane {
+    channels:
+        std::sync::Mutex<std::collections::HashMap<String, Vec<mpsc::UnboundedSender<String>>>>,
+}
+
+#[cfg(test)]
+impl Backplane for InProcessBackplane {
+    fn publish(&self, channel: &str, payload: String) {
+        if let Some(subscribers) = self.channels.lock().unwrap().get_mut(channel) {
+            subscribers.retain(|e| e.send(payload.clone()).is_ok());
+        }
+    }
+
+    fn subscribe(&self, channel: &str) -> mpsc::UnboundedReceiver<String> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.channels
+            .lock()
+            .unwrap()
+            .entry(channel.to_string())
+            .or_default()
+            .push(tx);
+        rx
+    }
+}
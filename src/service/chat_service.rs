@@ -1,93 +1,379 @@
-use std::collections::HashMap;
-use std::ops::Deref;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::thread;
 
-use crate::command;
-use crate::model::{Room, RoomInfo};
-use crate::service::{ChatRoom, ServiceError};
+use futures::future::join_all;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+use crate::{command, log};
+use crate::config::STATUS_TIMEOUT;
+use crate::model::{CreateMode, Namespaces, Room, RoomInfo, RoomUpdate};
+use crate::service::{ChatRoom, Replication, ServiceError};
+use crate::service::backplane::{RegistrySync, ROOMS_CHANNEL};
 
 command! {
-    pub CreateRoom(room: Room) -> Result<(), ServiceError>;
+    pub CreateRoom(room: Room) -> Result<ChatRoom, ServiceError>;
     pub Status()  -> Vec<RoomInfo>;
     pub GetRoom(room: String) -> Result<ChatRoom, ServiceError>;
-    pub DestroyRoom(room: String, secret: String) -> Result<(), ServiceError>;
+    /// Without a `secret`, e.g. for admins, the room is destroyed whatever its secret is.
+    pub DestroyRoom(room: String, secret: Option<String>) -> Result<(), ServiceError>;
+    pub Rooms(prefix: String) -> Vec<(String, ChatRoom)>;
+    pub DestroyRooms(prefix: String) -> usize;
+    ReplicateRoom(room: Room);
+    EvictRoom(room: String);
+    AnnounceRooms();
 }
 
+/// Rooms are spread over one registry actor per core, so joins to different rooms don't queue
+/// behind each other.
+#[derive(Clone)]
 pub struct ChatService {
-    pub op: CommandSender,
+    shards: Vec<CommandSender>,
+    namespaces: Arc<Namespaces>,
 }
 
 impl ChatService {
-    pub fn create() -> ChatService {
-        let (op, mut rx) = Command::new_channel();
-        let app = ChatService { op: op.clone() };
-        tokio::spawn(async move {
-            use Command::*;
-            let mut state = ChatServiceInner::default();
+    /// With a [Replication], rooms are also created and destroyed on the other instances.
+    pub fn create(replication: Option<Replication>, namespaces: Namespaces) -> ChatService {
+        let count = thread::available_parallelism().map_or(1, |e| e.get());
+        let namespaces = Arc::new(namespaces);
+        let service = ChatService {
+            shards: (0..count)
+                .map(|_| spawn_shard(replication.clone(), namespaces.clone()))
+                .collect(),
+            namespaces,
+        };
+        if let Some(replication) = replication {
+            let mut subscription = replication.subscribe(ROOMS_CHANNEL);
+            let this = service.clone();
+            tokio::spawn(async move {
+                while let Some(sync) = subscription.recv().await {
+                    this.on_sync(sync).await;
+                }
+            });
+            replication.publish(ROOMS_CHANNEL, &RegistrySync::Hello);
+        }
+        service
+    }
 
-            while let Some(command) = rx.recv().await {
-                match command {
-                    CreateRoom { room, resp_tx } => {
-                        let _ = resp_tx.send(state.create_room(room));
-                    }
-                    Status { resp_tx } => {
-                        let _ = resp_tx.send(state.status().await);
-                    }
-                    GetRoom { room, resp_tx } => {
-                        let _ = resp_tx.send(state.get_room(&room));
-                    }
-                    DestroyRoom {
-                        room,
-                        secret,
-                        resp_tx,
-                    } => {
-                        let _ = resp_tx.send(state.destroy_room(room, secret));
+    async fn on_sync(&self, sync: RegistrySync) {
+        match sync {
+            RegistrySync::Created { room } => {
+                let _ = self.shard(&room.uid).ReplicateRoom(room).await;
+            }
+            RegistrySync::Destroyed { room } => {
+                let _ = self.shard(&room).EvictRoom(room).await;
+            }
+            RegistrySync::Hello => {
+                for shard in &self.shards {
+                    let _ = shard.AnnounceRooms().await;
+                }
+            }
+        }
+    }
+
+    pub fn namespaces(&self) -> &Namespaces {
+        &self.namespaces
+    }
+
+    /// The registry actor owning the room `uid`.
+    pub fn shard(&self, uid: &str) -> &CommandSender {
+        let mut hasher = DefaultHasher::new();
+        uid.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    /// Creates the room, or according to `mode` returns the existing one. `true` if it was
+    /// created.
+    pub async fn create_room(
+        &self,
+        room: Room,
+        mode: CreateMode,
+    ) -> Result<(ChatRoom, bool), ServiceError> {
+        let shard = self.shard(&room.uid);
+        let chat_room = match shard.GetRoom(room.uid.clone()).await? {
+            Ok(_) if mode == CreateMode::Create => return Err(ServiceError::RoomAlreadyExists),
+            Ok(chat_room) => chat_room,
+            Err(_) => {
+                self.check_room_limits(&room.uid).await?;
+                match shard.CreateRoom(room.clone()).await? {
+                    Err(ServiceError::RoomAlreadyExists) if mode != CreateMode::Create => {
+                        shard.GetRoom(room.uid.clone()).await??
                     }
+                    result => return result.map(|e| (e, true)),
                 }
             }
-        });
+        };
+        if !chat_room.is_secret(&room.secret) {
+            return Err(ServiceError::SecretNotMatch);
+        }
+        let differences = chat_room.op.Snapshot().await?.differences(&room);
+        if !differences.is_empty() {
+            if mode == CreateMode::Idempotent {
+                return Err(ServiceError::SettingsConflict(differences));
+            }
+            let update = RoomUpdate {
+                secret: None,
+                post: Some(room.post.unwrap_or_default()),
+                post_types: Some(room.post_types),
+                slow_consumer: Some(room.slow_consumer),
+                slow_mode: Some(room.slow_mode),
+                expires_at: Some(room.expires_at),
+                idle_timeout: Some(room.idle_timeout.unwrap_or(0)),
+                max_participants: Some(room.max_participants.unwrap_or(0)),
+                overflow: Some(room.overflow),
+                presence_threshold: Some(room.presence_threshold.unwrap_or(0)),
+            };
+            chat_room.op.Update(update).await?;
+        }
+        Ok((chat_room, false))
+    }
+
+    /// Rooms being created at the same time are not counted, a namespace can end up with a few
+    /// more rooms than it allows.
+    async fn check_room_limits(&self, uid: &str) -> Result<(), ServiceError> {
+        for namespace in self.namespaces.of(uid) {
+            if let Some(max_rooms) = namespace.max_rooms {
+                if self.rooms(&namespace.prefix).await.len() >= max_rooms {
+                    return Err(ServiceError::NamespaceFull);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rooms under `prefix` (see [Room::is_under]), ordered by uid.
+    pub async fn rooms(&self, prefix: &str) -> Vec<(String, ChatRoom)> {
+        let mut result: Vec<(String, ChatRoom)> =
+            join_all(self.shards.iter().map(|e| e.Rooms(prefix.to_string())))
+                .await
+                .into_iter()
+                .flatten()
+                .flatten()
+                .collect();
+        result.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        result
+    }
+
+    /// The number of rooms under `prefix` and the status of the requested ones.
+    pub async fn status_page(
+        &self,
+        prefix: &str,
+        offset: usize,
+        limit: usize,
+    ) -> (usize, Vec<RoomInfo>) {
+        let rooms = self.rooms(prefix).await;
+        let page = rooms
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(_, e)| e.clone())
+            .collect();
+        (rooms.len(), status(page).await)
+    }
+
+    /// Destroys the rooms under `prefix` without their secret, returns how many.
+    pub async fn destroy_rooms(&self, prefix: &str) -> usize {
+        join_all(
+            self.shards
+                .iter()
+                .map(|e| e.DestroyRooms(prefix.to_string())),
+        )
+        .await
+        .into_iter()
+        .flatten()
+        .sum()
+    }
+
+    /// Announces to the rooms under `prefix`, returns how many.
+    pub async fn announce(&self, prefix: &str, kind: &str, text: &str) -> usize {
+        let rooms = self.rooms(prefix).await;
+        let results = join_all(
+            rooms
+                .iter()
+                .map(|(_, e)| e.op.Announce(kind.to_string(), text.to_string())),
+        )
+        .await;
+        results.into_iter().flatten().count()
+    }
 
-        app
+    pub async fn status(&self) -> Vec<RoomInfo> {
+        join_all(self.shards.iter().map(|e| e.Status()))
+            .await
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect()
     }
 }
 
-#[derive(Default)]
+fn spawn_shard(replication: Option<Replication>, namespaces: Arc<Namespaces>) -> CommandSender {
+    let (op, mut rx) = Command::new_channel();
+    tokio::spawn(async move {
+        use Command::*;
+        let (on_exit, mut exited) = mpsc::unbounded_channel();
+        let mut state = ChatServiceInner {
+            rooms: HashMap::new(),
+            origins: HashSet::new(),
+            replication,
+            namespaces,
+            on_exit,
+        };
+
+        loop {
+            let command = tokio::select! {
+                command = rx.recv() => command,
+                Some(uid) = exited.recv() => {
+                    state.remove_exited(&uid);
+                    continue;
+                }
+            };
+            let Some(command) = command else {
+                break;
+            };
+            match command {
+                CreateRoom { room, resp_tx } => {
+                    let _ = resp_tx.send(state.create_room(room));
+                }
+                Status { resp_tx } => {
+                    // rooms answer on their own, the registry keeps serving meanwhile
+                    let rooms: Vec<ChatRoom> = state.rooms.values().cloned().collect();
+                    tokio::spawn(async move {
+                        let _ = resp_tx.send(status(rooms).await);
+                    });
+                }
+                GetRoom { room, resp_tx } => {
+                    let _ = resp_tx.send(state.get_room(&room));
+                }
+                DestroyRoom {
+                    room,
+                    secret,
+                    resp_tx,
+                } => {
+                    let _ = resp_tx.send(state.destroy_room(room, secret));
+                }
+                Rooms { prefix, resp_tx } => {
+                    let _ = resp_tx.send(state.rooms(&prefix));
+                }
+                DestroyRooms { prefix, resp_tx } => {
+                    let _ = resp_tx.send(state.destroy_rooms(&prefix));
+                }
+                ReplicateRoom { room, resp_tx } => {
+                    state.replicate_room(room);
+                    let _ = resp_tx.send(());
+                }
+                EvictRoom { room, resp_tx } => {
+                    state.evict_room(&room);
+                    let _ = resp_tx.send(());
+                }
+                AnnounceRooms { resp_tx } => {
+                    state.announce_rooms();
+                    let _ = resp_tx.send(());
+                }
+            }
+        }
+    });
+
+    op
+}
+
+/// Rooms too busy to answer within [STATUS_TIMEOUT], or already gone, are left out.
+async fn status(rooms: Vec<ChatRoom>) -> Vec<RoomInfo> {
+    let results = join_all(rooms.iter().map(|e| timeout(STATUS_TIMEOUT, e.op.Status()))).await;
+    let result: Vec<RoomInfo> = results.into_iter().flatten().flatten().collect();
+    if result.len() < rooms.len() {
+        log!(
+            "status: {} rooms did not answer",
+            rooms.len() - result.len()
+        );
+    }
+    result
+}
+
 struct ChatServiceInner {
     rooms: HashMap<String, ChatRoom>,
+    // rooms created by a request to this instance, the others hold replicas
+    origins: HashSet<String>,
+    replication: Option<Replication>,
+    namespaces: Arc<Namespaces>,
+    // where rooms tell they stopped
+    on_exit: mpsc::UnboundedSender<String>,
 }
 
 impl ChatServiceInner {
-    async fn status(&self) -> Vec<RoomInfo> {
-        let mut result = Vec::new();
-        for e in self.rooms.values() {
-            result.push(e.op.Status().await)
+    fn create_room(&mut self, room: Room) -> Result<ChatRoom, ServiceError> {
+        if !Room::is_valid_uid(&room.uid) {
+            Err(ServiceError::InvalidRoomName)
+        } else if self.get_room(&room.uid).is_ok() {
+            Err(ServiceError::RoomAlreadyExists)
+        } else {
+            let uid = room.uid.clone();
+            if let Some(replication) = &self.replication {
+                replication.publish(ROOMS_CHANNEL, &RegistrySync::Created { room: room.clone() });
+                self.origins.insert(uid.clone());
+            }
+            let namespace = self.namespaces.settings(&uid);
+            let chat_room = ChatRoom::create(
+                room,
+                namespace,
+                self.replication.clone(),
+                false,
+                self.on_exit.clone(),
+            );
+            self.rooms.insert(uid, chat_room.clone());
+            Ok(chat_room)
         }
-        result
     }
 
-    fn create_room(&mut self, room: Room) -> Result<(), ServiceError> {
-        if self.rooms.contains_key(&room.uid) {
-            Err(ServiceError::RoomNotFound)
-        } else {
+    fn replicate_room(&mut self, room: Room) {
+        if self.get_room(&room.uid).is_err() {
             let uid = room.uid.clone();
-            let chat_room = ChatRoom::create(room);
+            let namespace = self.namespaces.settings(&uid);
+            let chat_room = ChatRoom::create(
+                room,
+                namespace,
+                self.replication.clone(),
+                true,
+                self.on_exit.clone(),
+            );
             self.rooms.insert(uid, chat_room);
-            Ok(())
         }
     }
 
+    /// Rooms are asked for their settings, which may have changed since they were created.
+    fn announce_rooms(&self) {
+        let Some(replication) = self.replication.clone() else {
+            return;
+        };
+        let rooms: Vec<ChatRoom> = self
+            .origins
+            .iter()
+            .filter_map(|e| self.rooms.get(e).cloned())
+            .collect();
+        tokio::spawn(async move {
+            for room in rooms {
+                if let Ok(room) = room.op.Snapshot().await {
+                    replication.publish(ROOMS_CHANNEL, &RegistrySync::Created { room });
+                }
+            }
+        });
+    }
+
     fn get_room(&self, room: &str) -> Result<ChatRoom, ServiceError> {
-        if let Some(instance) = self.rooms.get(room) {
+        if let Some(instance) = self.rooms.get(room).filter(|e| !e.op.is_closed()) {
             Ok(instance.clone())
         } else {
             Err(ServiceError::RoomNotFound)
         }
     }
 
-    fn destroy_room(&mut self, uid: String, secret: String) -> Result<(), ServiceError> {
+    fn destroy_room(&mut self, uid: String, secret: Option<String>) -> Result<(), ServiceError> {
         if let Some(room) = self.rooms.get(&uid) {
-            if room.secret.deref() == &secret {
-                room.op.spawn().Destroy();
-                self.rooms.remove(&uid);
+            if secret.is_none_or(|e| room.is_secret(&e)) {
+                self.destroy(&uid);
                 Ok(())
             } else {
                 Err(ServiceError::SecretNotMatch)
@@ -96,4 +382,408 @@ impl ChatServiceInner {
             Err(ServiceError::RoomNotFound)
         }
     }
+
+    fn rooms(&self, prefix: &str) -> Vec<(String, ChatRoom)> {
+        self.rooms
+            .iter()
+            .filter(|(uid, room)| Room::is_under(uid, prefix) && !room.op.is_closed())
+            .map(|(uid, room)| (uid.clone(), room.clone()))
+            .collect()
+    }
+
+    fn destroy_rooms(&mut self, prefix: &str) -> usize {
+        let uids: Vec<String> = self.rooms(prefix).into_iter().map(|(e, _)| e).collect();
+        for uid in &uids {
+            self.destroy(uid);
+        }
+        uids.len()
+    }
+
+    /// Destroys the room here and on the other instances.
+    fn destroy(&mut self, uid: &str) {
+        if let Some(replication) = &self.replication {
+            let sync = RegistrySync::Destroyed {
+                room: uid.to_string(),
+            };
+            replication.publish(ROOMS_CHANNEL, &sync);
+        }
+        self.evict_room(uid);
+    }
+
+    /// Forgets a room that stopped by itself, e.g. expired.
+    fn remove_exited(&mut self, uid: &str) {
+        if self.rooms.get(uid).is_some_and(|e| e.op.is_closed()) {
+            self.rooms.remove(uid);
+            if let Some(replication) = &self.replication {
+                if self.origins.remove(uid) {
+                    let sync = RegistrySync::Destroyed {
+                        room: uid.to_string(),
+                    };
+                    replication.publish(ROOMS_CHANNEL, &sync);
+                }
+            }
+        }
+    }
+
+    fn evict_room(&mut self, uid: &str) {
+        if let Some(room) = self.rooms.remove(uid) {
+            room.op.spawn().Destroy();
+        }
+        self.origins.remove(uid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::misc::ActorGone;
+    use crate::model::{Namespace, OverflowPolicy, Participant, RoomUpdate};
+    use crate::service::backplane::{room_channel, InProcessBackplane, RoomSync};
+    use crate::service::Backplane;
+
+    async fn get_room(service: &ChatService, uid: &str) -> Result<ChatRoom, ServiceError> {
+        service.shard(uid).GetRoom(uid.to_string()).await.unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_sharded_rooms() {
+        let service = ChatService::create(None, Namespaces::default());
+        for i in 0..20 {
+            let uid = format!("/app/room{i}");
+            service
+                .shard(&uid)
+                .CreateRoom(Room::new(&uid, "secret"))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        assert!(get_room(&service, "/app/room7").await.is_ok());
+        let uid = "/app/room7".to_string();
+        let result = service
+            .shard(&uid)
+            .CreateRoom(Room::new(&uid, "secret"))
+            .await
+            .unwrap();
+        assert!(matches!(result, Err(ServiceError::RoomAlreadyExists)));
+        assert!(get_room(&service, "/app/none").await.is_err());
+        assert_eq!(service.status().await.len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_create_modes() {
+        let service = ChatService::create(None, Namespaces::default());
+        let uid = "/app/room";
+        let (_, created) = service
+            .create_room(Room::new(uid, "secret"), CreateMode::Create)
+            .await
+            .unwrap();
+        assert!(created);
+        let result = service
+            .create_room(Room::new(uid, "secret"), CreateMode::Create)
+            .await;
+        assert!(matches!(result, Err(ServiceError::RoomAlreadyExists)));
+
+        // a retry
+        let (_, created) = service
+            .create_room(Room::new(uid, "secret"), CreateMode::Idempotent)
+            .await
+            .unwrap();
+        assert!(!created);
+        let other = Room {
+            slow_mode: 5,
+            ..Room::new(uid, "secret")
+        };
+        let result = service
+            .create_room(other.clone(), CreateMode::Idempotent)
+            .await;
+        assert!(matches!(result, Err(ServiceError::SettingsConflict(e)) if e == ["slowMode"]));
+        let stranger = Room {
+            secret: "other".to_string(),
+            ..Room::new(uid, "secret")
+        };
+        let result = service.create_room(stranger, CreateMode::Ensure).await;
+        assert!(matches!(result, Err(ServiceError::SecretNotMatch)));
+
+        let (chat_room, created) = service
+            .create_room(other, CreateMode::Ensure)
+            .await
+            .unwrap();
+        assert!(!created);
+        assert_eq!(chat_room.op.Snapshot().await.unwrap().slow_mode, 5);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_clears_settings() {
+        let service = ChatService::create(None, Namespaces::default());
+        let uid = "/app/room";
+        let configured = Room {
+            expires_at: Some(Utc::now() + chrono::Duration::seconds(3600)),
+            idle_timeout: Some(60),
+            presence_threshold: Some(10),
+            ..Room::new(uid, "secret")
+        };
+        service
+            .create_room(configured, CreateMode::Create)
+            .await
+            .unwrap();
+
+        let (chat_room, _) = service
+            .create_room(Room::new(uid, "secret"), CreateMode::Ensure)
+            .await
+            .unwrap();
+        let room = chat_room.op.Snapshot().await.unwrap();
+        assert_eq!(room.expires_at, None);
+        assert_eq!(room.idle_timeout, None);
+        assert_eq!(room.presence_threshold, None);
+        let (_, created) = service
+            .create_room(Room::new(uid, "secret"), CreateMode::Idempotent)
+            .await
+            .unwrap();
+        assert!(!created);
+    }
+
+    #[tokio::test]
+    async fn test_namespace_limits() {
+        let dev = Namespace {
+            prefix: "/dev".to_string(),
+            max_rooms: Some(2),
+            max_participants: Some(0),
+            ..Default::default()
+        };
+        let service = ChatService::create(None, Namespaces::new(vec![dev]).unwrap());
+        for uid in ["/dev/1", "/dev/2", "/devices/1"] {
+            service
+                .create_room(Room::new(uid, "secret"), CreateMode::Create)
+                .await
+                .unwrap();
+        }
+        let result = service
+            .create_room(Room::new("/dev/3", "secret"), CreateMode::Create)
+            .await;
+        assert!(matches!(result, Err(ServiceError::NamespaceFull)));
+        let (chat_room, created) = service
+            .create_room(Room::new("/dev/2", "secret"), CreateMode::Idempotent)
+            .await
+            .unwrap();
+        assert!(!created);
+        let result = chat_room.op.CanJoin(None).await.unwrap();
+        assert!(matches!(result, Err(ServiceError::RoomFull)));
+        let chat_room = get_room(&service, "/devices/1").await.unwrap();
+        assert!(chat_room.op.CanJoin(None).await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_participant_limits() {
+        let service = ChatService::create(None, Namespaces::default());
+        let full = Room {
+            max_participants: Some(0),
+            ..Room::new("/app/full", "secret")
+        };
+        let (chat_room, _) = service
+            .create_room(full.clone(), CreateMode::Create)
+            .await
+            .unwrap();
+        let result = chat_room.op.CanJoin(None).await.unwrap();
+        assert!(matches!(result, Err(ServiceError::RoomFull)));
+
+        let viewers = Room {
+            overflow: OverflowPolicy::Viewers,
+            ..full
+        };
+        let result = service
+            .create_room(viewers.clone(), CreateMode::Idempotent)
+            .await;
+        assert!(matches!(result, Err(ServiceError::SettingsConflict(e)) if e == ["overflow"]));
+        service
+            .create_room(viewers, CreateMode::Ensure)
+            .await
+            .unwrap();
+        assert!(chat_room.op.CanJoin(None).await.unwrap().is_ok());
+
+        let update = RoomUpdate {
+            max_participants: Some(0),
+            overflow: Some(OverflowPolicy::Reject),
+            ..Default::default()
+        };
+        chat_room.op.Update(update).await.unwrap();
+        assert!(chat_room.op.CanJoin(None).await.unwrap().is_ok());
+        assert_eq!(
+            chat_room.op.Snapshot().await.unwrap().max_participants,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bulk_administration() {
+        let service = ChatService::create(None, Namespaces::default());
+        for uid in ["/dev/2", "/dev/1", "/devices/1", "/prod/1"] {
+            service
+                .create_room(Room::new(uid, "secret"), CreateMode::Create)
+                .await
+                .unwrap();
+        }
+        let uids: Vec<String> = service
+            .rooms("/dev")
+            .await
+            .into_iter()
+            .map(|(e, _)| e)
+            .collect();
+        assert_eq!(uids, ["/dev/1", "/dev/2"]);
+        let (total, page) = service.status_page("/", 1, 2).await;
+        assert_eq!(total, 4);
+        assert_eq!(page.len(), 2);
+        assert_eq!(service.announce("/dev", "news", "hello").await, 2);
+
+        assert_eq!(service.destroy_rooms("/dev").await, 2);
+        assert_eq!(service.rooms("/").await.len(), 2);
+        assert!(get_room(&service, "/dev/1").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_destroyed_room() {
+        let service = ChatService::create(None, Namespaces::default());
+        let uid = "/app/room";
+        service
+            .shard(uid)
+            .CreateRoom(Room::new(uid, "secret"))
+            .await
+            .unwrap()
+            .unwrap();
+        let chat_room = get_room(&service, uid).await.unwrap();
+        chat_room.op.Destroy().await.unwrap();
+        wait_until(|| async { chat_room.op.Count().await.is_err() }).await;
+        assert!(matches!(chat_room.op.Status().await, Err(ActorGone)));
+    }
+
+    #[tokio::test]
+    async fn test_expired_room() {
+        let service = ChatService::create(None, Namespaces::default());
+        let uid = "/app/room";
+        let idle = Room {
+            idle_timeout: Some(0),
+            ..Room::new(uid, "secret")
+        };
+        service.shard(uid).CreateRoom(idle).await.unwrap().unwrap();
+        wait_until(|| async { get_room(&service, uid).await.is_err() }).await;
+        // the uid is free again
+        service
+            .shard(uid)
+            .CreateRoom(Room::new(uid, "secret"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(get_room(&service, uid).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_updated_room() {
+        let backplane: Arc<dyn Backplane> = Arc::new(InProcessBackplane::default());
+        let a = ChatService::create(
+            Some(Replication::new(backplane.clone())),
+            Namespaces::default(),
+        );
+        let b = ChatService::create(
+            Some(Replication::new(backplane.clone())),
+            Namespaces::default(),
+        );
+        let uid = "/app/room";
+        a.shard(uid)
+            .CreateRoom(Room::new(uid, "secret"))
+            .await
+            .unwrap()
+            .unwrap();
+        wait_until(|| async { get_room(&b, uid).await.is_ok() }).await;
+
+        let chat_room = get_room(&a, uid).await.unwrap();
+        let update = RoomUpdate {
+            slow_mode: Some(5),
+            ..Default::default()
+        };
+        chat_room.op.Update(update).await.unwrap();
+        chat_room
+            .op
+            .RotateSecret("rotated".to_string())
+            .await
+            .unwrap();
+        assert!(!chat_room.is_secret("secret"));
+        assert!(chat_room.is_secret("rotated"));
+        let replica = get_room(&b, uid).await.unwrap();
+        wait_until(|| async { replica.is_secret("rotated") }).await;
+        assert_eq!(replica.op.Snapshot().await.unwrap().slow_mode, 5);
+
+        let result = a
+            .shard(uid)
+            .DestroyRoom(uid.to_string(), Some("secret".to_string()))
+            .await
+            .unwrap();
+        assert!(matches!(result, Err(ServiceError::SecretNotMatch)));
+    }
+
+    async fn wait_until<F: Future<Output = bool>>(mut condition: impl FnMut() -> F) {
+        for _ in 0..200 {
+            if condition().await {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("timed out");
+    }
+
+    #[tokio::test]
+    async fn test_replicated_rooms() {
+        let backplane: Arc<dyn Backplane> = Arc::new(InProcessBackplane::default());
+        let a = ChatService::create(
+            Some(Replication::new(backplane.clone())),
+            Namespaces::default(),
+        );
+        let b = ChatService::create(
+            Some(Replication::new(backplane.clone())),
+            Namespaces::default(),
+        );
+        let uid = "/app/room";
+        a.shard(uid)
+            .CreateRoom(Room::new(uid, "secret"))
+            .await
+            .unwrap()
+            .unwrap();
+        wait_until(|| async { get_room(&b, uid).await.is_ok() }).await;
+
+        let other = Replication::new(backplane.clone());
+        other.publish(
+            &room_channel(uid),
+            &RoomSync::Presence {
+                instance: other.origin().to_string(),
+                participants: vec![Participant {
+                    username: Some("someone".to_string()),
+                    display: None,
+                }],
+            },
+        );
+        for service in [&a, &b] {
+            let room = get_room(service, uid).await.unwrap();
+            wait_until(|| async { room.op.Count().await.unwrap() == 1 }).await;
+        }
+
+        // a late instance is told about the existing rooms
+        let c = ChatService::create(
+            Some(Replication::new(backplane.clone())),
+            Namespaces::default(),
+        );
+        wait_until(|| async { get_room(&c, uid).await.is_ok() }).await;
+
+        b.shard(uid)
+            .DestroyRoom(uid.to_string(), Some("secret".to_string()))
+            .await
+            .unwrap()
+            .unwrap();
+        for service in [&a, &c] {
+            wait_until(|| async { get_room(service, uid).await.is_err() }).await;
+        }
+    }
 }
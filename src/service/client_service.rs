@@ -1,46 +1,258 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
 use futures::sink::SinkExt;
-use hyper_tungstenite::tungstenite::Message;
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+use crate::log;
+use crate::config::{CLIENT_QUEUE_SIZE, SLOW_CONSUMER_CLOSE_CODE};
+use crate::misc::{OrEmpty, random_token, WebSocketSink};
+use crate::model::{Participant, SlowConsumerPolicy};
 
-use crate::{command, log};
-use crate::misc::{OrEmpty, WebSocketSink};
-use crate::model::Participant;
+/// Events dropped for slow clients, server wide.
+pub static DROPPED_EVENTS: AtomicU64 = AtomicU64::new(0);
+/// Clients disconnected for being too slow, server wide.
+pub static SLOW_CONSUMERS: AtomicU64 = AtomicU64::new(0);
+
+/// Same counters as [DROPPED_EVENTS] and [SLOW_CONSUMERS], shared by the clients of a room.
+#[derive(Default)]
+pub struct OutboundMetrics {
+    pub dropped: AtomicU64,
+    pub slow_consumers: AtomicU64,
+}
 
 pub struct ChatClient {
-    pub op: CommandSender,
+    outbox: OutboxHandle,
     pub me: Participant,
+    pub last_typing: Option<Instant>,
+    pub last_message: Option<Instant>,
+    pub resume_token: String,
 }
 
 impl ChatClient {
-    pub fn new(sink: WebSocketSink, me: Participant) -> ChatClient {
-        let (op, mut rx) = Command::new_channel();
+    pub fn new(
+        sink: WebSocketSink,
+        me: Participant,
+        policy: SlowConsumerPolicy,
+        metrics: Arc<OutboundMetrics>,
+    ) -> ChatClient {
+        let outbox = Arc::new(Outbox {
+            state: Mutex::new(OutboxState::default()),
+            notify: Notify::new(),
+            policy,
+            metrics,
+        });
 
-        let mut inner = ClientInner { sink };
+        let inner = ClientInner { sink };
         let debug_name = me.display.or_empty().to_string();
+        let writer = outbox.clone();
         tokio::spawn(async move {
-            while let Some(command) = rx.recv().await {
-                match command {
-                    Command::Send { message, resp_tx } => {
-                        let _ = resp_tx.send(inner.send(message).await);
-                    }
-                }
-            }
+            inner.run(writer).await;
             log!("client `{debug_name}` dropped")
         });
 
-        ChatClient { op, me }
+        ChatClient {
+            outbox: OutboxHandle(outbox),
+            me,
+            last_typing: None,
+            last_message: None,
+            resume_token: random_token(),
+        }
+    }
+
+    /// Queues a text that must reach the client.
+    ///
+    /// The buffer is shared with the other recipients all the way to the socket, it is never
+    /// copied per client.
+    pub fn send(&self, text: Utf8Bytes) {
+        self.outbox.0.push(text, true);
+    }
+
+    /// Queues a text that may be dropped if the client is too slow.
+    pub fn send_droppable(&self, text: Utf8Bytes) {
+        self.outbox.0.push(text, false);
+    }
+
+    /// Closes the connection once the queued texts are written, which starts when the room drops
+    /// the client.
+    pub fn close(&self, code: u16, reason: &'static str) {
+        self.outbox.0.state.lock().unwrap().close = Some(CloseFrame {
+            code: CloseCode::from(code),
+            reason: reason.into(),
+        });
     }
 }
 
-pub struct ClientInner {
-    sink: WebSocketSink,
+/// Lets the writer stop once the room drops the client.
+struct OutboxHandle(Arc<Outbox>);
+
+impl Drop for OutboxHandle {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().is_detached = true;
+        self.0.notify.notify_one();
+    }
+}
+
+struct Outbox {
+    state: Mutex<OutboxState>,
+    notify: Notify,
+    policy: SlowConsumerPolicy,
+    metrics: Arc<OutboundMetrics>,
+}
+
+#[derive(Default)]
+struct OutboxState {
+    // texts and whether they are critical
+    queue: VecDeque<(Utf8Bytes, bool)>,
+    // the client could not keep up, its connection must be closed
+    is_slow: bool,
+    // the room let go of the client, stop once the queue is flushed
+    is_detached: bool,
+    // sent before stopping
+    close: Option<CloseFrame>,
+}
+
+impl Outbox {
+    fn push(&self, text: Utf8Bytes, critical: bool) {
+        let mut state = self.state.lock().unwrap();
+        if state.is_slow {
+            return;
+        }
+        if state.queue.len() >= CLIENT_QUEUE_SIZE {
+            let droppable = match self.policy {
+                SlowConsumerPolicy::DropOldest => state.queue.iter().position(|(_, e)| !e),
+                SlowConsumerPolicy::Disconnect => None,
+            };
+            match droppable {
+                Some(index) => {
+                    state.queue.remove(index);
+                    self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+                    DROPPED_EVENTS.fetch_add(1, Ordering::Relaxed);
+                }
+                None => {
+                    state.is_slow = true;
+                    state.queue.clear();
+                    self.metrics.slow_consumers.fetch_add(1, Ordering::Relaxed);
+                    SLOW_CONSUMERS.fetch_add(1, Ordering::Relaxed);
+                    drop(state);
+                    self.notify.notify_one();
+                    return;
+                }
+            }
+        }
+        state.queue.push_back((text, critical));
+        drop(state);
+        self.notify.notify_one();
+    }
 }
 
-command! {
-    pub Send(message: Message);
+pub struct ClientInner {
+    sink: WebSocketSink,
 }
 
 impl ClientInner {
+    /// Writes queued messages one at a time, which keeps them in order.
+    async fn run(mut self, outbox: Arc<Outbox>) {
+        loop {
+            let (text, is_slow, is_detached) = {
+                let mut state = outbox.state.lock().unwrap();
+                (state.queue.pop_front(), state.is_slow, state.is_detached)
+            };
+            if is_slow {
+                let frame = CloseFrame {
+                    code: CloseCode::from(SLOW_CONSUMER_CLOSE_CODE),
+                    reason: "slow consumer".into(),
+                };
+                self.send(Message::Close(Some(frame))).await;
+                break;
+            }
+            match text {
+                Some((text, _)) => self.send(Message::Text(text)).await,
+                None if is_detached => {
+                    let close = outbox.state.lock().unwrap().close.take();
+                    if let Some(frame) = close {
+                        self.send(Message::Close(Some(frame))).await;
+                    }
+                    break;
+                }
+                None => outbox.notify.notified().await,
+            }
+        }
+    }
+
     async fn send(&mut self, message: Message) {
         let _ = self.sink.send(message).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbox(policy: SlowConsumerPolicy) -> Outbox {
+        Outbox {
+            state: Mutex::new(OutboxState::default()),
+            notify: Notify::new(),
+            policy,
+            metrics: Arc::default(),
+        }
+    }
+
+    #[test]
+    fn test_full_queue() {
+        let drop_oldest = outbox(SlowConsumerPolicy::DropOldest);
+        drop_oldest.push("banned".into(), true);
+        for i in 0..CLIENT_QUEUE_SIZE {
+            drop_oldest.push(i.to_string().into(), false);
+        }
+        let state = drop_oldest.state.lock().unwrap();
+        assert!(!state.is_slow);
+        assert_eq!(state.queue.len(), CLIENT_QUEUE_SIZE);
+        assert_eq!(&*state.queue[0].0, "banned");
+        assert_eq!(&*state.queue[1].0, "1");
+        assert_eq!(drop_oldest.metrics.dropped.load(Ordering::Relaxed), 1);
+
+        let disconnect = outbox(SlowConsumerPolicy::Disconnect);
+        for i in 0..=CLIENT_QUEUE_SIZE {
+            disconnect.push(i.to_string().into(), false);
+        }
+        let state = disconnect.state.lock().unwrap();
+        assert!(state.is_slow);
+        assert!(state.queue.is_empty());
+        assert_eq!(disconnect.metrics.slow_consumers.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let content = serde_json::to_string(&serde_json::json!({
+            "textroom": "message",
+            "from": "someone",
+            "text": "x".repeat(200),
+        }))
+        .unwrap();
+        let outboxes: Vec<Outbox> = (0..100).map(|_| outbox(Default::default())).collect();
+
+        for _ in 0..3 {
+            let text = Utf8Bytes::from(content.clone());
+            for outbox in &outboxes {
+                outbox.push(text.clone(), false);
+            }
+            // every message handed to the sink still points to the buffer of the room
+            assert!(outboxes
+                .iter()
+                .map(next_message)
+                .all(|e| e.to_text().unwrap().as_ptr() == text.as_ptr()));
+        }
+    }
+
+    /// What the writer task hands to the sink next.
+    fn next_message(outbox: &Outbox) -> Message {
+        let (text, _) = outbox.state.lock().unwrap().queue.pop_front().unwrap();
+        Message::Text(text)
+    }
+}
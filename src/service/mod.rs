@@ -1,9 +1,14 @@
+pub use backplane::{Backplane, Replication};
 pub use chat_service::ChatService;
+pub use client_service::{DROPPED_EVENTS, SLOW_CONSUMERS};
+pub use redis_backplane::RedisBackplane;
 pub use room_service::ChatRoom;
 pub use service_error::ServiceError;
 
+mod backplane;
 mod chat_service;
 mod client_service;
+mod redis_backplane;
 mod rest_client;
 mod room_service;
 mod service_error;
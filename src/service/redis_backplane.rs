@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::io;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use urlencoding::decode;
+
+use crate::log;
+use crate::config::BACKPLANE_RECONNECT_DELAY;
+use crate::service::backplane::Backplane;
+
+type Subscribe = (String, mpsc::UnboundedSender<String>);
+
+/// [Backplane] over the pub/sub commands of a Redis server.
+///
+/// Publications share one pipelined connection and all the channels share one subscriber
+/// connection. Both reconnect on failure, publications wait meanwhile and what the subscriber
+/// misses is lost.
+pub struct RedisBackplane {
+    publisher: mpsc::UnboundedSender<(String, String)>,
+    subscriber: mpsc::UnboundedSender<Subscribe>,
+}
+
+impl RedisBackplane {
+    /// `url` is `redis://[[username]:password@]host[:port][/db]`, the credentials being sent
+    /// with `AUTH` on every connection.
+    pub fn connect(url: &str) -> Result<RedisBackplane, String> {
+        let url = RedisUrl::parse(url)?;
+        println!("backplane: {}", url.addr);
+        let (publisher, rx) = mpsc::unbounded_channel();
+        tokio::spawn(run_publisher(url.clone(), rx));
+        let (subscriber, rx) = mpsc::unbounded_channel();
+        tokio::spawn(run_subscriber(url, rx));
+        Ok(RedisBackplane {
+            publisher,
+            subscriber,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct RedisUrl {
+    addr: String,
+    // arguments of the `AUTH` command
+    auth: Option<Vec<String>>,
+}
+
+impl RedisUrl {
+    fn parse(url: &str) -> Result<RedisUrl, String> {
+        let rest = match url.split_once("://") {
+            None => url,
+            Some(("redis", rest)) => rest,
+            Some((scheme, _)) => {
+                return Err(format!("backplane scheme `{scheme}` is not supported"))
+            }
+        };
+        // the database doesn't matter to pub/sub
+        let authority = rest.split('/').next().unwrap_or_default();
+        let (userinfo, host) = match authority.rsplit_once('@') {
+            Some((userinfo, host)) => (Some(userinfo), host),
+            None => (None, authority),
+        };
+        if host.is_empty() || host.starts_with(':') {
+            return Err("backplane host is missing".to_string());
+        }
+        let has_port = match host.rfind(']') {
+            Some(index) => host[index..].contains(':'),
+            None => host.contains(':'),
+        };
+        let addr = if has_port {
+            host.to_string()
+        } else {
+            format!("{host}:6379")
+        };
+        let auth = match userinfo.map(|e| e.split_once(':')) {
+            None => None,
+            Some(None) => return Err("backplane password is missing".to_string()),
+            Some(Some((username, password))) => {
+                let unescape = |e| decode(e).map(|e| e.into_owned()).map_err(|e| e.to_string());
+                let mut args = vec!["AUTH".to_string()];
+                if !username.is_empty() {
+                    args.push(unescape(username)?);
+                }
+                args.push(unescape(password)?);
+                Some(args)
+            }
+        };
+        Ok(RedisUrl { addr, auth })
+    }
+}
+
+/// Opens a connection, authenticated when the url has credentials.
+async fn open(url: &RedisUrl) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(&url.addr).await?;
+    if let Some(auth) = &url.auth {
+        let args: Vec<&str> = auth.iter().map(|e| e.as_str()).collect();
+        stream.write_all(&command(&args)).await?;
+        // nothing else was sent, so the reader can't buffer past the reply
+        read_frame(&mut BufReader::new(&mut stream)).await?;
+    }
+    Ok(stream)
+}
+
+impl Backplane for RedisBackplane {
+    fn publish(&self, channel: &str, payload: String) {
+        let _ = self.publisher.send((channel.to_string(), payload));
+    }
+
+    fn subscribe(&self, channel: &str) -> mpsc::UnboundedReceiver<String> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let _ = self.subscriber.send((channel.to_string(), tx));
+        rx
+    }
+}
+
+async fn run_publisher(url: RedisUrl, mut rx: mpsc::UnboundedReceiver<(String, String)>) {
+    let mut connection: Option<OwnedWriteHalf> = None;
+    while let Some((channel, payload)) = rx.recv().await {
+        let command = command(&["PUBLISH", &channel, &payload]);
+        loop {
+            let write = match connection.take() {
+                Some(write) => write,
+                None => match open(&url).await {
+                    Ok(stream) => {
+                        let (read, write) = stream.into_split();
+                        // replies are not needed, they only have to be consumed
+                        tokio::spawn(async move {
+                            let mut reader = BufReader::new(read);
+                            while read_frame(&mut reader).await.is_ok() {}
+                        });
+                        write
+                    }
+                    Err(error) => {
+                        log!("redis publisher: {error}");
+                        tokio::time::sleep(BACKPLANE_RECONNECT_DELAY).await;
+                        continue;
+                    }
+                },
+            };
+            let write = connection.insert(write);
+            match write.write_all(&command).await {
+                Ok(()) => break,
+                Err(error) => {
+                    log!("redis publisher: {error}");
+                    connection = None;
+                }
+            }
+        }
+    }
+}
+
+async fn run_subscriber(url: RedisUrl, mut rx: mpsc::UnboundedReceiver<Subscribe>) {
+    let mut channels: HashMap<String, Vec<mpsc::UnboundedSender<String>>> = HashMap::new();
+    loop {
+        let (read, mut write) = match open(&url).await {
+            Ok(stream) => stream.into_split(),
+            Err(error) => {
+                log!("redis subscriber: {error}");
+                tokio::time::sleep(BACKPLANE_RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        let (frames_tx, mut frames) = mpsc::unbounded_channel();
+        tokio::spawn(read_frames(read, frames_tx));
+
+        let mut is_connected = channels.is_empty() || {
+            let mut args = vec!["SUBSCRIBE"];
+            args.extend(channels.keys().map(|e| e.as_str()));
+            write.write_all(&command(&args)).await.is_ok()
+        };
+        while is_connected {
+            tokio::select! {
+                request = rx.recv() => {
+                    let Some((channel, tx)) = request else {
+                        return;
+                    };
+                    let subscribers = channels.entry(channel.clone()).or_default();
+                    subscribers.push(tx);
+                    if subscribers.len() == 1 {
+                        is_connected = write.write_all(&command(&["SUBSCRIBE", &channel])).await.is_ok();
+                    }
+                }
+                frame = frames.recv() => {
+                    let Some(frame) = frame else {
+                        break;
+                    };
+                    let [kind, channel, payload] = frame.as_slice() else {
+                        continue;
+                    };
+                    if kind != "message" {
+                        continue;
+                    }
+                    if let Some(subscribers) = channels.get_mut(channel) {
+                        subscribers.retain(|e| e.send(payload.clone()).is_ok());
+                        if subscribers.is_empty() {
+                            channels.remove(channel);
+                            is_connected = write.write_all(&command(&["UNSUBSCRIBE", channel])).await.is_ok();
+                        }
+                    }
+                }
+            }
+        }
+        log!("redis subscriber disconnected");
+        tokio::time::sleep(BACKPLANE_RECONNECT_DELAY).await;
+    }
+}
+
+async fn read_frames(read: OwnedReadHalf, tx: mpsc::UnboundedSender<Vec<String>>) {
+    let mut reader = BufReader::new(read);
+    loop {
+        match read_frame(&mut reader).await {
+            Ok(frame) => {
+                if tx.send(frame).is_err() {
+                    break;
+                }
+            }
+            Err(error) => {
+                log!("redis subscriber: {error}");
+                break;
+            }
+        }
+    }
+}
+
+/// Encodes a command as an array of bulk strings.
+fn command(args: &[&str]) -> Vec<u8> {
+    let mut result = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        result.extend(format!("${}\r\n", arg.len()).as_bytes());
+        result.extend(arg.as_bytes());
+        result.extend(b"\r\n");
+    }
+    result
+}
+
+/// Reads a reply, pub/sub replies never nest arrays so their items are returned flat.
+async fn read_frame<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Vec<String>> {
+    let line = read_line(reader).await?;
+    if let Some(len) = line.strip_prefix('*') {
+        let len: i64 = len.parse().map_err(invalid_data)?;
+        let mut result = Vec::new();
+        for _ in 0..len.max(0) {
+            let line = read_line(reader).await?;
+            result.push(read_scalar(reader, line).await?);
+        }
+        Ok(result)
+    } else {
+        Ok(vec![read_scalar(reader, line).await?])
+    }
+}
+
+async fn read_scalar<R: AsyncBufRead + Unpin>(reader: &mut R, line: String) -> io::Result<String> {
+    let (kind, value) = line.split_at(line.len().min(1));
+    match kind {
+        "+" | ":" => Ok(value.to_string()),
+        "-" => Err(io::Error::other(value.to_string())),
+        "$" => {
+            let len: i64 = value.parse().map_err(invalid_data)?;
+            if len < 0 {
+                return Ok(String::new());
+            }
+            let mut buf = vec![0; len as usize + 2];
+            reader.read_exact(&mut buf).await?;
+            buf.truncate(len as usize);
+            String::from_utf8(buf).map_err(invalid_data)
+        }
+        _ => Err(invalid_data(format!("unexpected reply `{line}`"))),
+    }
+}
+
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(line.trim_end_matches("\r\n").to_string())
+}
+
+fn invalid_data<E: ToString>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let result = command(&["PUBLISH", "rooms", "{}"]);
+        assert_eq!(
+            result,
+            b"*3\r\n$7\r\nPUBLISH\r\n$5\r\nrooms\r\n$2\r\n{}\r\n"
+        );
+    }
+
+    #[test]
+    fn test_redis_url() {
+        let url = |addr: &str, auth: Option<&[&str]>| RedisUrl {
+            addr: addr.to_string(),
+            auth: auth.map(|e| e.iter().map(|e| e.to_string()).collect()),
+        };
+        assert_eq!(RedisUrl::parse("redis://host"), Ok(url("host:6379", None)));
+        assert_eq!(RedisUrl::parse("host:6380"), Ok(url("host:6380", None)));
+        assert_eq!(
+            RedisUrl::parse("redis://:p%40ss@host:6380/0"),
+            Ok(url("host:6380", Some(&["AUTH", "p@ss"])))
+        );
+        assert_eq!(
+            RedisUrl::parse("redis://user:pass@[::1]"),
+            Ok(url("[::1]:6379", Some(&["AUTH", "user", "pass"])))
+        );
+        assert!(RedisUrl::parse("redis://user@host").is_err());
+        assert!(RedisUrl::parse("redis://:pass@").is_err());
+        assert!(RedisUrl::parse("rediss://host").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_frame() {
+        let mut input: &[u8] =
+            b"*3\r\n$9\r\nsubscribe\r\n$5\r\nrooms\r\n:1\r\n*3\r\n$7\r\nmessage\r\n$5\r\nrooms\r\n$4\r\na\r\nb\r\n:2\r\n-ERR nope\r\n";
+        assert_eq!(
+            read_frame(&mut input).await.unwrap(),
+            ["subscribe", "rooms", "1"]
+        );
+        assert_eq!(
+            read_frame(&mut input).await.unwrap(),
+            ["message", "rooms", "a\r\nb"]
+        );
+        assert_eq!(read_frame(&mut input).await.unwrap(), ["2"]);
+        assert!(read_frame(&mut input).await.is_err());
+    }
+}
@@ -2,6 +2,7 @@ use std::io::Write;
 use std::sync::Arc;
 
 use bytes::BufMut;
+use hmac::{Hmac, Mac};
 use http_body_util::BodyExt;
 use hyper::{Request, Response, StatusCode};
 use hyper::body::Incoming;
@@ -10,11 +11,14 @@ use hyper_util::client::legacy::Client;
 use hyper_util::client::legacy::connect::HttpConnector;
 use hyper_util::rt::TokioExecutor;
 use serde::Serialize;
+use sha2::Sha256;
 
 use crate::log;
+use crate::config::SIGNATURE_HEADER;
 
 pub struct RestClient {
     post_url: String,
+    signing_keys: Vec<String>,
     client: Arc<Client<HttpsConnector<HttpConnector>, String>>,
 }
 #[cfg(debug_assertions)]
@@ -23,7 +27,7 @@ async fn read_body(response: &mut Response<Incoming>) -> String {
     while let Some(next) = response.frame().await {
         if let Ok(frame) = next {
             if let Some(chunk) = frame.data_ref() {
-                buf.write_all(&chunk).unwrap();
+                buf.write_all(chunk).unwrap();
             }
         } else {
             break;
@@ -37,9 +41,10 @@ async fn read_body(response: &mut Response<Incoming>) -> String {
 }
 
 impl RestClient {
-    pub fn create(post_url: String) -> RestClient {
+    pub fn create(post_url: String, signing_keys: Vec<String>) -> RestClient {
         RestClient {
             post_url,
+            signing_keys,
             client: Arc::new(Client::builder(TokioExecutor::new()).build(HttpsConnector::new())),
         }
     }
@@ -48,12 +53,15 @@ impl RestClient {
     where
         M: Serialize,
     {
-        let request = Request::builder()
+        let body = serde_json::to_string(message).unwrap();
+        let mut builder = Request::builder()
             .uri(&self.post_url)
             .method(hyper::Method::POST)
-            .header(hyper::header::CONTENT_TYPE, "application/json")
-            .body(serde_json::to_string(message).unwrap())
-            .unwrap();
+            .header(hyper::header::CONTENT_TYPE, "application/json");
+        if !self.signing_keys.is_empty() {
+            builder = builder.header(SIGNATURE_HEADER, signature(&self.signing_keys, &body));
+        }
+        let request = builder.body(body).unwrap();
         let client = self.client.clone();
         tokio::spawn(async move {
             match client.request(request).await {
@@ -72,3 +80,38 @@ impl RestClient {
         });
     }
 }
+
+fn signature(keys: &[String], body: &str) -> String {
+    let signatures: Vec<String> = keys
+        .iter()
+        .map(|key| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).unwrap();
+            mac.update(body.as_bytes());
+            let hex: String = mac
+                .finalize()
+                .into_bytes()
+                .iter()
+                .map(|e| format!("{e:02x}"))
+                .collect();
+            format!("sha256={hex}")
+        })
+        .collect();
+    signatures.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature() {
+        let keys = ["key".to_string(), "old".to_string()];
+        let result = signature(&keys, "The quick brown fox jumps over the lazy dog");
+        let (first, second) = result.split_once(", ").unwrap();
+        assert_eq!(
+            first,
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        assert!(second.starts_with("sha256=") && second != first);
+    }
+}
@@ -1,22 +1,33 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::Ordering;
 use std::time::Instant;
 
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use futures::{StreamExt, TryStreamExt};
-use hyper_tungstenite::tungstenite::error::ProtocolError;
-use hyper_tungstenite::tungstenite::Message as WsMessage;
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::error::ProtocolError;
+use tokio_tungstenite::tungstenite::{Message as WsMessage, Utf8Bytes};
 
 use crate::{command, log};
-use crate::config::PORT;
+use crate::config::{
+    BURST_MAX_COUNT, BURST_MAX_KINDS, EVENT_LOG_SIZE, HISTORY_SIZE, PINNED_MAX, PIN_MAX_DURATION,
+    POLL_DEFAULT_DURATION, POLL_MAX_CLOSED, POLL_MAX_DURATION, POLL_MAX_OPTIONS, PORT,
+    PRESENCE_DELTA_MAX, PRESENCE_REFRESH, PRESENCE_THRESHOLD, PRESENCE_TTL, RESUME_GRACE,
+    REACTION_MAX_LEN, READ_RECEIPTS_MAX_PARTICIPANTS, REPLAY_MAX, ROOM_TICK_INTERVAL,
+    SESSION_RESUMED_CLOSE_CODE, TYPING_MAX_USERS, TYPING_THROTTLE, WELCOME_MAX_PARTICIPANTS,
+};
 use crate::misc::*;
 use crate::model::{
-    JoinParams, Message, Participant, Room, RoomInfo, TextRoomEvent, TextRoomRequest,
-    TextRoomResponse,
+    ChatMessage, ErrorCode, JoinParams, Message, Namespace, OverflowPolicy, Participant,
+    PinnedMessage, Poll, PollResults, Room, RoomInfo, RoomSettings, RoomUpdate, Sequenced,
+    TextRoomEvent, TextRoomRequest, TextRoomResponse, Typer, Unread, Welcome,
 };
-use crate::service::client_service::ChatClient;
+use crate::service::{Replication, ServiceError};
+use crate::service::backplane::{room_channel, RoomSync, Subscription};
+use crate::service::client_service::{ChatClient, OutboundMetrics};
 use crate::service::rest_client::RestClient;
 
 command! {
@@ -24,28 +35,56 @@ command! {
     pub Count() -> usize;
     pub Join(sink: WebSocketSink, params: JoinParams) -> usize;
     pub LastAnnouncement(types: Vec<String>) -> HashMap<String, String>;
-    pub Participants() -> Vec<Participant>;
+    pub Participants(search: Option<String>, offset: usize, limit: Option<usize>) -> (usize, Vec<Participant>);
     pub Photo(username: String) -> Option<String>;
+    pub History(limit: Option<usize>) -> Vec<ChatMessage>;
+    pub ReadMarkers(username: Option<String>) -> HashMap<String, u64>;
+    pub Thread(id: u64) -> Option<Vec<ChatMessage>>;
+    pub Pin(id: u64, expires_in: Option<u64>) -> Result<(), ServiceError>;
+    pub Unpin(id: u64) -> Result<(), ServiceError>;
+    pub Pinned() -> Vec<PinnedMessage>;
+    pub OpenPoll(question: String, options: Vec<String>, duration: Option<u64>, multiple: bool, answers: Vec<usize>) -> Result<Poll, ServiceError>;
+    pub ClosePoll(id: u64) -> Result<(), ServiceError>;
+    pub Polls() -> Vec<Poll>;
     pub Destroy();
+    pub Update(update: RoomUpdate);
+    pub RotateSecret(secret: String);
+    pub Snapshot() -> Room;
+    pub Announce(kind: String, text: String);
+    pub CanJoin(resume: Option<String>) -> Result<(), ServiceError>;
     OnMessageReceived(sender_id:usize, message: WsMessage);
     Leave(id: usize);
 }
 
 #[derive(Clone)]
 pub struct ChatRoom {
-    pub secret: Arc<String>,
+    // written by the room when the secret is rotated
+    secret: Arc<RwLock<String>>,
     pub op: CommandSender,
 }
 
 impl ChatRoom {
-    pub fn create(room: Room) -> ChatRoom {
+    /// A replica is a room created on another instance, it doesn't post the room's lifecycle.
+    ///
+    /// The uid is sent to `on_exit` once the room stopped, e.g. after it expired.
+    pub fn create(
+        room: Room,
+        namespace: Namespace,
+        replication: Option<Replication>,
+        is_replica: bool,
+        on_exit: mpsc::UnboundedSender<String>,
+    ) -> ChatRoom {
         let (op, mut rx) = Command::new_channel();
+        let secret = Arc::new(RwLock::new(room.secret.clone()));
         let chat_room = ChatRoom {
             op,
-            secret: Arc::new(room.secret.clone()),
+            secret: secret.clone(),
         };
         tokio::spawn(async move {
-            let mut state = ChatRoomInner::new(room);
+            let mut subscription = replication
+                .as_ref()
+                .map(|e| e.subscribe(&room_channel(&room.uid)));
+            let mut state = ChatRoomInner::new(room, namespace, secret, replication, is_replica);
             log!("`room {}` created", &state.room.uid);
             log!(
                 "To destroy: http://127.0.0.1:{}{}/destroy?secret={}",
@@ -53,8 +92,31 @@ impl ChatRoom {
                 &state.room.uid,
                 &state.room.secret
             );
-            state.post_created();
-            while let Some(command) = rx.recv().await {
+            if !is_replica {
+                state.post_created();
+            }
+            let mut ticker = tokio::time::interval(ROOM_TICK_INTERVAL);
+            loop {
+                let command = tokio::select! {
+                    command = rx.recv() => command,
+                    sync = next_sync(&mut subscription) => {
+                        match sync {
+                            Some(sync) => state.on_sync(sync),
+                            None => subscription = None,
+                        }
+                        continue;
+                    }
+                    _ = ticker.tick() => {
+                        state.on_tick();
+                        if state.is_destroyed {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                let Some(command) = command else {
+                    break;
+                };
                 match command {
                     Command::Status { resp_tx } => {
                         let _ = resp_tx.send(state.status());
@@ -70,56 +132,141 @@ impl ChatRoom {
                         let _ = resp_tx.send(state.join(sink, params));
                     }
                     Command::Leave { id, resp_tx } => {
-                        state.leave(id);
+                        state.suspend(id);
+                        let _ = resp_tx.send(());
+                    }
+                    Command::Update { update, resp_tx } => {
+                        state.update(update);
+                        let _ = resp_tx.send(());
+                    }
+                    Command::RotateSecret { secret, resp_tx } => {
+                        state.rotate_secret(secret);
                         let _ = resp_tx.send(());
                     }
+                    Command::Announce {
+                        kind,
+                        text,
+                        resp_tx,
+                    } => {
+                        state.announce_as(ADMIN_SENDER, kind, text);
+                        let _ = resp_tx.send(());
+                    }
+                    Command::CanJoin { resume, resp_tx } => {
+                        let _ = resp_tx.send(state.can_join(resume.as_deref()));
+                    }
+                    Command::Snapshot { resp_tx } => {
+                        let _ = resp_tx.send(state.room.clone());
+                    }
                     Command::Destroy { resp_tx } => {
                         state.destroy();
                         let _ = resp_tx.send(());
+                        // later commands fail with `ActorGone`
+                        break;
                     }
                     Command::LastAnnouncement { types, resp_tx } => {
                         let _ = resp_tx.send(state.last_announcement(types));
                     }
-                    Command::Participants { resp_tx } => {
-                        let _ = resp_tx.send(state.participants());
+                    Command::Participants {
+                        search,
+                        offset,
+                        limit,
+                        resp_tx,
+                    } => {
+                        let page = state.participants_page(search.as_deref(), offset, limit);
+                        let _ = resp_tx.send(page);
                     }
                     Command::Photo { username, resp_tx } => {
                         let photo = state.photos.get(&username).map(|e| e.to_owned());
                         let _ = resp_tx.send(photo);
                     }
+                    Command::History { limit, resp_tx } => {
+                        let _ = resp_tx.send(state.history(limit));
+                    }
+                    Command::ReadMarkers { username, resp_tx } => {
+                        let _ = resp_tx.send(state.read_markers(username));
+                    }
+                    Command::Thread { id, resp_tx } => {
+                        let _ = resp_tx.send(state.thread(id));
+                    }
+                    Command::Pin {
+                        id,
+                        expires_in,
+                        resp_tx,
+                    } => {
+                        let _ = resp_tx.send(state.pin(id, expires_in));
+                    }
+                    Command::Unpin { id, resp_tx } => {
+                        let _ = resp_tx.send(state.unpin(id));
+                    }
+                    Command::Pinned { resp_tx } => {
+                        let _ = resp_tx.send(state.pinned.clone());
+                    }
+                    Command::OpenPoll {
+                        question,
+                        options,
+                        duration,
+                        multiple,
+                        answers,
+                        resp_tx,
+                    } => {
+                        let poll = state.open_poll(question, options, duration, multiple, answers);
+                        let _ = resp_tx.send(poll);
+                    }
+                    Command::ClosePoll { id, resp_tx } => {
+                        let _ = resp_tx.send(state.close_poll(id));
+                    }
+                    Command::Polls { resp_tx } => {
+                        let _ = resp_tx.send(state.polls.clone());
+                    }
                     Command::OnMessageReceived {
                         sender_id,
                         message,
                         resp_tx,
                     } => {
-                        let _ = resp_tx.send(state.on_message_received(sender_id, message).await);
+                        state.on_message_received(sender_id, message).await;
+                        let _ = resp_tx.send(());
                     }
                 }
             }
             log!("room `{}` dropped", &state.room.uid);
+            drop(rx);
+            let _ = on_exit.send(state.room.uid.clone());
         });
 
         chat_room
     }
 
+    pub fn is_secret(&self, secret: &str) -> bool {
+        is_same_token(&self.secret.read().unwrap(), secret)
+    }
+
     pub async fn join(
         &self,
         mut req: HttpRequest,
         params: JoinParams,
     ) -> Result<HttpResponse, ProtocolError> {
-        use hyper_tungstenite::*;
-        if is_upgrade_request(&req) {
-            let (response, socket) = upgrade(&mut req, None)?;
+        if websocket::is_upgrade_request(&req) {
+            let (response, on_upgrade) = websocket::upgrade(&mut req)?;
             let this = self.clone();
 
             tokio::spawn(async move {
-                // FIXME: do not unwrap
-                let (sink, mut stream) = socket.await.unwrap().split();
-                let id = this.op.Join(sink, params).await;
+                let socket = match websocket::accept(on_upgrade).await {
+                    Ok(socket) => socket,
+                    Err(error) => {
+                        log!("upgrade failed: {error}");
+                        return;
+                    }
+                };
+                let (sink, mut stream) = socket.split();
+                let Ok(id) = this.op.Join(Box::pin(sink), params).await else {
+                    return;
+                };
                 while let Some(message) = stream.try_next().await.ok().flatten() {
-                    this.op.OnMessageReceived(id, message).await;
+                    if this.op.OnMessageReceived(id, message).await.is_err() {
+                        return;
+                    }
                 }
-                this.op.Leave(id).await;
+                let _ = this.op.Leave(id).await;
             });
             Ok(response)
         } else {
@@ -132,77 +279,385 @@ impl ChatRoom {
 
 struct ChatRoomInner {
     room: Room,
+    // settings of the namespace of the room
+    namespace: Namespace,
+    // same as [self.room.secret], shared with [ChatRoom]
+    secret: Arc<RwLock<String>>,
     clients: HashMap<usize, ChatClient>,
+    // overflow clients of a full room, they only receive the room events
+    viewers: HashMap<usize, ChatClient>,
+    // dropped connections that can still be resumed, by resume token
+    suspended: HashMap<String, Suspended>,
     photos: HashMap<String, String>,
     last_announcements: HashMap<String, String>,
+    // clients whose `typing` is waiting for the next tick
+    typing: Vec<usize>,
+    // `burst` taps waiting for the next tick
+    bursts: BTreeMap<String, usize>,
+    // recent messages, ordered by id
+    history: VecDeque<ChatMessage>,
+    // ordered by pin date
+    pinned: Vec<PinnedMessage>,
+    // open and closed polls, ordered by id
+    polls: Vec<Poll>,
+    // serialized room events, ordered by seq
+    events: VecDeque<(u64, Utf8Bytes)>,
+    // last message id read, by username
+    read_markers: HashMap<String, u64>,
     rest_client: Option<RestClient>,
+    replication: Option<Replication>,
+    is_replica: bool,
+    // participants of the other instances, by instance
+    remote: HashMap<String, RemotePresence>,
+    // joins and leaves waiting for the next tick, in large rooms
+    presence_delta: PresenceDelta,
+    // joins and leaves not told to the other instances yet
+    unpublished_presence: PresenceDelta,
+    next_presence: Instant,
+    next_refresh: Instant,
+    // replicas expire when the instance that created the room stops refreshing it
+    origin_deadline: Instant,
+    // since when the room has no participants
+    idle_since: Option<Instant>,
+    // outbound queue counters of all clients
+    metrics: Arc<OutboundMetrics>,
     // cache value from [self.room.name()]
     room_name: String,
     messages: usize,
     next_id: usize,
+    // highest message id of the room, whichever instance assigned it
+    last_message_id: u64,
+    next_poll_id: u64,
+    // number of the last room event
+    seq: u64,
     is_destroyed: bool,
 }
 
 impl ChatRoomInner {
-    fn new(room: Room) -> Self {
-        let rest_client = match &room.post {
-            None => None,
-            Some(post) => Some(RestClient::create(post.clone())),
-        };
+    fn new(
+        room: Room,
+        namespace: Namespace,
+        secret: Arc<RwLock<String>>,
+        replication: Option<Replication>,
+        is_replica: bool,
+    ) -> Self {
+        let rest_client = rest_client(&room, &namespace);
         ChatRoomInner {
             room_name: room.name().to_string(),
             room,
+            namespace,
+            secret,
             clients: HashMap::new(),
+            viewers: HashMap::new(),
+            suspended: HashMap::new(),
             last_announcements: HashMap::new(),
+            typing: Vec::new(),
+            bursts: BTreeMap::new(),
+            history: VecDeque::new(),
+            read_markers: HashMap::new(),
+            events: VecDeque::new(),
+            pinned: Vec::new(),
+            polls: Vec::new(),
             photos: HashMap::new(),
             next_id: 0,
+            last_message_id: 0,
+            next_poll_id: 0,
+            seq: 0,
             messages: 0,
             is_destroyed: false,
             rest_client,
+            replication,
+            is_replica,
+            remote: HashMap::new(),
+            presence_delta: PresenceDelta::default(),
+            unpublished_presence: PresenceDelta::default(),
+            next_presence: Instant::now(),
+            next_refresh: Instant::now(),
+            origin_deadline: Instant::now() + PRESENCE_TTL,
+            idle_since: None,
+            metrics: Arc::default(),
         }
     }
     fn join(&mut self, socket: WebSocketSink, params: JoinParams) -> usize {
         self.next_id += 1;
         let id = self.next_id;
-        let me = Participant {
+        let resumed = params
+            .resume
+            .as_deref()
+            .and_then(|token| self.take_session(token));
+        let is_resumed = resumed.is_some();
+        let me = resumed.unwrap_or(Participant {
             username: params.username,
             display: params.display,
-        };
-        let client = ChatClient::new(socket, me);
-        if client.me.username.is_some() && params.image_url.is_some() {
-            self.photos.insert(
-                client.me.username.clone().unwrap(),
-                params.image_url.unwrap(),
+        });
+        let client = ChatClient::new(socket, me, self.room.slow_consumer, self.metrics.clone());
+        if !is_resumed && self.is_full() && self.room.overflow == OverflowPolicy::Viewers {
+            log!(
+                "'{}' joined as a viewer (id: {id})",
+                client.me.display.or_empty()
             );
+            self.viewers.insert(id, client);
+            self.welcome(id, false, false);
+            return id;
+        }
+        if let (Some(username), Some(image_url)) = (&client.me.username, params.image_url) {
+            self.photos.insert(username.clone(), image_url);
+        }
+        if !is_resumed {
+            self.broadcast_presence(&client.me, true);
         }
-        let participant = &client.me;
-        let event = &TextRoomEvent::Joined {
-            username: participant.username.as_deref(),
-            display: participant.display.as_deref(),
-            participants: self.count(),
-        };
-        self.broadcast_json(event);
         log!(
             "'{}' joined (id: {}, count:{})",
-            participant.display.or_empty(),
+            client.me.display.or_empty(),
             id,
             self.clients.len() + 1
         );
 
         self.clients.insert(id, client);
+        let since = params.last.filter(|_| is_resumed).unwrap_or(self.seq);
+        self.welcome(id, is_resumed, !self.can_replay(since));
+        self.replay(id, since);
         id
     }
 
+    /// Takes the identity of a suspended session, or of a connection not closed yet.
+    fn take_session(&mut self, token: &str) -> Option<Participant> {
+        if let Some(session) = self.suspended.remove(token) {
+            return Some(session.me);
+        }
+        let id = self
+            .clients
+            .iter()
+            .find(|(_, e)| e.resume_token == token)
+            .map(|(id, _)| *id)?;
+        let client = self.clients.remove(&id)?;
+        client.close(SESSION_RESUMED_CLOSE_CODE, "session resumed");
+        Some(client.me)
+    }
+
+    /// Whether every room event numbered after `since` is still kept, and they are few enough to
+    /// be replayed without overflowing the queue of the client.
+    fn can_replay(&self, since: u64) -> bool {
+        since.saturating_add(self.events.len() as u64) >= self.seq
+            && self.seq.saturating_sub(since) <= REPLAY_MAX as u64
+    }
+
+    /// Sends again the kept room events numbered after `since`, at most the last [REPLAY_MAX].
+    fn replay(&self, id: usize, since: u64) {
+        let since = since.max(self.seq.saturating_sub(REPLAY_MAX as u64));
+        if let Some(client) = self.client(id) {
+            for (_, content) in self.events.iter().filter(|(seq, _)| *seq > since) {
+                client.send_droppable(content.clone());
+            }
+        }
+    }
+
+    /// Keeps the identity of a dropped connection for a while, without telling anyone.
+    fn suspend(&mut self, id: usize) {
+        if self.viewers.remove(&id).is_some() {
+            return;
+        }
+        if let Some(client) = self.clients.remove(&id) {
+            log!("`{}` disconnected", client.me.display.or_empty());
+            self.suspended.insert(
+                client.resume_token,
+                Suspended {
+                    me: client.me,
+                    deadline: Instant::now() + RESUME_GRACE,
+                },
+            );
+        }
+    }
+
+    fn expire_sessions(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .suspended
+            .iter()
+            .filter(|(_, e)| e.deadline <= now)
+            .map(|(token, _)| token.clone())
+            .collect();
+        for token in expired {
+            self.drop_session(&token);
+        }
+    }
+
+    fn drop_session(&mut self, token: &str) {
+        if let Some(session) = self.suspended.remove(token) {
+            log!("`{}` left (suspended)", session.me.display.or_empty());
+            self.broadcast_presence(&session.me, false);
+        }
+    }
+
+    fn welcome(&self, id: usize, resumed: bool, gap: bool) {
+        let Some(client) = self.client(id) else {
+            return;
+        };
+        let unread = client
+            .me
+            .username
+            .as_ref()
+            .and_then(|username| self.read_markers.get(username))
+            .map(|last_read| Unread {
+                last_read: *last_read,
+                count: self
+                    .history
+                    .iter()
+                    .rev()
+                    .take_while(|e| e.id > *last_read)
+                    .count() as u64,
+            });
+        let participants = if self.count() <= WELCOME_MAX_PARTICIPANTS {
+            Some(self.participant_list().collect())
+        } else {
+            None
+        };
+        self.reply_json(
+            id,
+            &Welcome {
+                id,
+                resume: &client.resume_token,
+                resumed,
+                seq: self.seq,
+                gap,
+                count: self.count(),
+                viewer: self.viewers.contains_key(&id),
+                participants,
+                announcements: &self.last_announcements,
+                pinned: &self.pinned,
+                polls: self.polls.iter().filter(|e| !e.closed).collect(),
+                unread,
+                settings: self.settings(),
+            },
+        );
+    }
+
+    fn settings(&self) -> RoomSettings {
+        RoomSettings {
+            typing_throttle: TYPING_THROTTLE.as_secs(),
+            read_receipts: self.count() <= READ_RECEIPTS_MAX_PARTICIPANTS,
+            history_size: HISTORY_SIZE,
+            slow_mode: self.room.slow_mode,
+            presence_threshold: self.presence_threshold(),
+        }
+    }
+
+    fn presence_threshold(&self) -> usize {
+        self.room.presence_threshold.unwrap_or(PRESENCE_THRESHOLD)
+    }
+
+    /// A `join` or `leave` event, or in large rooms an entry of the next `presence` event.
+    fn broadcast_presence(&mut self, participant: &Participant, joined: bool) {
+        if self.replication.is_some() {
+            self.unpublished_presence.push(participant, joined);
+        }
+        // a joining client is only added to the room afterwards
+        let participants = self.count() + usize::from(joined);
+        if participants >= self.presence_threshold() {
+            self.presence_delta.push(participant, joined);
+            return;
+        }
+        // the room just got smaller, what was batched goes first
+        self.flush_presence();
+        let username = participant.username.as_deref();
+        let display = participant.display.as_deref();
+        let event = if joined {
+            TextRoomEvent::Joined {
+                username,
+                display,
+                participants,
+            }
+        } else {
+            TextRoomEvent::Left {
+                username,
+                display,
+                participants,
+            }
+        };
+        self.broadcast_json(&event);
+    }
+
+    fn flush_presence(&mut self) {
+        if self.presence_delta.is_empty() {
+            return;
+        }
+        let delta = std::mem::take(&mut self.presence_delta);
+        self.broadcast_json(&TextRoomEvent::Presence {
+            joined: delta.joined,
+            left: delta.left,
+            others: delta.others,
+            participants: self.count(),
+        });
+    }
+
+    fn update(&mut self, update: RoomUpdate) {
+        self.replicate(&RoomSync::Updated {
+            update: update.clone(),
+        });
+        self.apply_update(update);
+        self.broadcast_json(&TextRoomEvent::SettingsChanged {
+            settings: self.settings(),
+        });
+    }
+
+    /// Connected clients stay, only the old secret stops working.
+    fn rotate_secret(&mut self, secret: String) {
+        let update = RoomUpdate {
+            secret: Some(secret),
+            ..Default::default()
+        };
+        self.replicate(&RoomSync::Updated {
+            update: update.clone(),
+        });
+        self.apply_update(update);
+    }
+
+    /// Applies an update made on this instance or on another one.
+    fn apply_update(&mut self, update: RoomUpdate) {
+        if let Some(secret) = update.secret {
+            secret.clone_into(&mut self.secret.write().unwrap());
+            self.room.secret = secret;
+        }
+        if let Some(post) = update.post {
+            self.room.post = Some(post).filter(|e| !e.is_empty());
+            self.rest_client = rest_client(&self.room, &self.namespace);
+        }
+        if let Some(post_types) = update.post_types {
+            self.room.post_types = post_types;
+        }
+        // clients already connected keep the policy they joined with
+        if let Some(slow_consumer) = update.slow_consumer {
+            self.room.slow_consumer = slow_consumer;
+        }
+        if let Some(slow_mode) = update.slow_mode {
+            self.room.slow_mode = slow_mode;
+        }
+        if let Some(expires_at) = update.expires_at {
+            self.room.expires_at = expires_at;
+        }
+        if let Some(idle_timeout) = update.idle_timeout {
+            self.room.idle_timeout = Some(idle_timeout).filter(|e| *e > 0);
+        }
+        // participants above a lowered limit stay
+        if let Some(max_participants) = update.max_participants {
+            self.room.max_participants = Some(max_participants).filter(|e| *e > 0);
+        }
+        if let Some(overflow) = update.overflow {
+            self.room.overflow = overflow;
+        }
+        if let Some(presence_threshold) = update.presence_threshold {
+            self.room.presence_threshold = Some(presence_threshold).filter(|e| *e > 0);
+        }
+    }
+
     fn leave(&mut self, id: usize) -> Option<ChatClient> {
+        if let Some(client) = self.viewers.remove(&id) {
+            return Some(client);
+        }
         if let Some(client) = self.clients.remove(&id) {
-            let len = self.clients.len();
+            let len = self.count();
             println!("`{}` left (size={len})", client.me.display.or_empty(),);
-            let event = &TextRoomEvent::Left {
-                username: client.me.username.as_deref(),
-                display: client.me.display.as_deref(),
-                participants: len,
-            };
-            self.broadcast(serde_json::to_string(event).unwrap());
+            self.broadcast_presence(&client.me, false);
             Some(client)
         } else {
             None
@@ -212,7 +667,7 @@ impl ChatRoomInner {
     async fn on_message_received(&mut self, sender_id: usize, message: WsMessage) {
         match message {
             WsMessage::Text(text) => {
-                self.on_listen(sender_id, text);
+                self.on_listen(sender_id, &text);
             }
             WsMessage::Binary(msg) => {
                 log!("unexpected binary message: {:02X?}", msg);
@@ -238,19 +693,22 @@ impl ChatRoomInner {
             WsMessage::Frame(_) => {}
         }
     }
-    fn on_listen(&mut self, sender_id: usize, message: String) {
+    fn on_listen(&mut self, sender_id: usize, message: &str) {
         log!("receive: {message}");
 
-        let response = match serde_json::from_str(&message) {
+        let response = match serde_json::from_str(message) {
             Ok(value) => self.handle_request(sender_id, value),
             Err(e) => {
                 eprintln!("parse json failed: {:?}", e);
-                serde_json::from_str::<UnknownTextRoomRequest>(&message)
+                serde_json::from_str::<UnknownTextRoomRequest>(message)
                     .map(|e| e.transaction)
                     .unwrap_or(None)
-                    .map(|transaction| TextRoomResponse::Error {
-                        transaction: Some(transaction),
-                        error: e.to_string(),
+                    .map(|transaction| {
+                        TextRoomResponse::error(
+                            Some(transaction),
+                            ErrorCode::BadRequest,
+                            e.to_string(),
+                        )
                     })
             }
         };
@@ -269,11 +727,25 @@ impl ChatRoomInner {
             return Some(TextRoomResponse::destroyed(request.transaction()));
         }
         log!("handling ws message: {:?}", &request);
+        if self.viewers.contains_key(&sender_id) {
+            return match request {
+                TextRoomRequest::Leave { transaction } => {
+                    self.leave(sender_id);
+                    Some(TextRoomResponse::left(transaction))
+                }
+                request => Some(error_response(
+                    request.transaction(),
+                    ServiceError::Forbidden,
+                )),
+            };
+        }
         match request {
-            TextRoomRequest::Message { r#type, text, .. } => {
-                self.send_message(sender_id, r#type, text);
-                None
-            }
+            TextRoomRequest::Message {
+                r#type,
+                text,
+                reply_to,
+                transaction,
+            } => self.send_message(sender_id, r#type, text, reply_to, transaction),
             TextRoomRequest::Announcement {
                 secret,
                 r#type,
@@ -281,11 +753,11 @@ impl ChatRoomInner {
                 transaction,
                 ..
             } => {
-                if self.room.secret.deref() == &secret {
+                if is_same_token(&self.room.secret, &secret) {
                     self.announce(sender_id, r#type, text);
                     None
                 } else {
-                    Some(TextRoomResponse::secret(transaction))
+                    Some(error_response(transaction, ServiceError::SecretNotMatch))
                 }
             }
             TextRoomRequest::Leave { transaction } => {
@@ -297,11 +769,95 @@ impl ChatRoomInner {
                 username,
                 transaction,
             } => {
-                if self.room.secret.deref() == &secret {
+                if is_same_token(&self.room.secret, &secret) {
                     self.ban(sender_id, username);
                     None
                 } else {
-                    Some(TextRoomResponse::secret(transaction))
+                    Some(error_response(transaction, ServiceError::SecretNotMatch))
+                }
+            }
+            TextRoomRequest::Typing { .. } => {
+                self.typing(sender_id);
+                None
+            }
+            TextRoomRequest::React {
+                id,
+                emoji,
+                remove,
+                transaction,
+            } => self.react(sender_id, id, emoji, remove, transaction),
+            TextRoomRequest::Burst {
+                emoji,
+                count,
+                transaction,
+            } => self.burst(emoji, count, transaction),
+            TextRoomRequest::Read { id, transaction } => self.read(sender_id, id, transaction),
+            TextRoomRequest::Pin {
+                id,
+                secret,
+                expires_in,
+                transaction,
+            } => {
+                if is_same_token(&self.room.secret, &secret) {
+                    self.pin(id, expires_in)
+                        .err()
+                        .map(|e| error_response(transaction, e))
+                } else {
+                    Some(error_response(transaction, ServiceError::SecretNotMatch))
+                }
+            }
+            TextRoomRequest::Unpin {
+                id,
+                secret,
+                transaction,
+            } => {
+                if is_same_token(&self.room.secret, &secret) {
+                    self.unpin(id).err().map(|e| error_response(transaction, e))
+                } else {
+                    Some(error_response(transaction, ServiceError::SecretNotMatch))
+                }
+            }
+            TextRoomRequest::Poll {
+                secret,
+                question,
+                options,
+                duration,
+                multiple,
+                answers,
+                transaction,
+            } => {
+                if is_same_token(&self.room.secret, &secret) {
+                    self.open_poll(question, options, duration, multiple, answers)
+                        .err()
+                        .map(|e| error_response(transaction, e))
+                } else {
+                    Some(error_response(transaction, ServiceError::SecretNotMatch))
+                }
+            }
+            TextRoomRequest::ClosePoll {
+                id,
+                secret,
+                transaction,
+            } => {
+                if is_same_token(&self.room.secret, &secret) {
+                    self.close_poll(id)
+                        .err()
+                        .map(|e| error_response(transaction, e))
+                } else {
+                    Some(error_response(transaction, ServiceError::SecretNotMatch))
+                }
+            }
+            TextRoomRequest::Vote {
+                poll,
+                options,
+                transaction,
+            } => self.vote(sender_id, poll, options, transaction),
+            TextRoomRequest::Replay { since, transaction } => {
+                if self.can_replay(since) {
+                    self.replay(sender_id, since);
+                    None
+                } else {
+                    Some(TextRoomResponse::events_unavailable(transaction))
                 }
             }
         }
@@ -310,23 +866,123 @@ impl ChatRoomInner {
     fn status(&self) -> RoomInfo {
         RoomInfo {
             room: self.room.uid.clone(),
-            participants: self.clients.iter().map(|(_, e)| e.me.clone()).collect(),
+            participants: self.participants(),
+            viewers: self.viewers.len(),
             messages: self.messages,
+            dropped: self.metrics.dropped.load(Ordering::Relaxed),
+            slow_consumers: self.metrics.slow_consumers.load(Ordering::Relaxed),
         }
     }
 
+    /// Suspended sessions still count until they expire.
     fn count(&self) -> usize {
-        self.clients.len()
+        let remote: usize = self.remote.values().map(|e| e.participants.len()).sum();
+        self.clients.len() + self.suspended.len() + remote
+    }
+
+    /// The lower of the limits of the room and of its namespace.
+    fn max_participants(&self) -> Option<usize> {
+        match (self.room.max_participants, self.namespace.max_participants) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.max_participants().is_some_and(|e| self.count() >= e)
+    }
+
+    /// Clients resuming a session get their place back. Others may still join a full room
+    /// meanwhile, which can overshoot the limit by a few.
+    fn can_join(&self, resume: Option<&str>) -> Result<(), ServiceError> {
+        let is_resuming = resume.is_some_and(|token| {
+            self.suspended.contains_key(token)
+                || self.clients.values().any(|e| e.resume_token == token)
+        });
+        if !is_resuming && self.is_full() && self.room.overflow == OverflowPolicy::Reject {
+            Err(ServiceError::RoomFull)
+        } else {
+            Ok(())
+        }
     }
 
     fn participants(&self) -> Vec<Participant> {
-        self.clients.iter().map(|(_, e)| e.me.clone()).collect()
+        self.participant_list().cloned().collect()
+    }
+
+    /// The participants matching `search`, if any, ordered by display name then username, and
+    /// how many they are.
+    fn participants_page(
+        &self,
+        search: Option<&str>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> (usize, Vec<Participant>) {
+        let search = search.filter(|e| !e.is_empty()).map(str::to_lowercase);
+        let mut matches: Vec<&Participant> = self
+            .participant_list()
+            .filter(|participant| {
+                let Some(search) = &search else {
+                    return true;
+                };
+                [&participant.username, &participant.display]
+                    .into_iter()
+                    .flatten()
+                    .any(|e| e.to_lowercase().contains(search))
+            })
+            .collect();
+        matches.sort_by(|a, b| (&a.display, &a.username).cmp(&(&b.display, &b.username)));
+        let page = matches
+            .iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|e| (*e).clone())
+            .collect();
+        (matches.len(), page)
+    }
+
+    fn participant_list(&self) -> impl Iterator<Item = &Participant> {
+        self.local_participants()
+            .chain(self.remote.values().flat_map(|e| &e.participants))
+    }
+
+    fn local_participants(&self) -> impl Iterator<Item = &Participant> {
+        self.clients
+            .values()
+            .map(|e| &e.me)
+            .chain(self.suspended.values().map(|e| &e.me))
+    }
+
+    fn client(&self, id: usize) -> Option<&ChatClient> {
+        self.clients.get(&id).or_else(|| self.viewers.get(&id))
     }
 
     fn participant_by_id(&self, id: usize) -> Option<&Participant> {
         self.clients.get(&id).map(|e| &e.me)
     }
 
+    fn history(&self, limit: Option<usize>) -> Vec<ChatMessage> {
+        let skip = limit.map_or(0, |limit| self.history.len().saturating_sub(limit));
+        self.history.iter().skip(skip).cloned().collect()
+    }
+
+    /// The first message of a thread followed by all its replies.
+    fn thread(&self, id: u64) -> Option<Vec<ChatMessage>> {
+        let root = &self.history[self.history_index(id)?];
+        let root_id = root.thread.unwrap_or(root.id);
+        Some(
+            self.history
+                .iter()
+                .filter(|e| e.id == root_id || e.thread == Some(root_id))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    fn history_index(&self, id: u64) -> Option<usize> {
+        self.history.binary_search_by_key(&id, |e| e.id).ok()
+    }
+
     fn last_announcement(&self, types: Vec<String>) -> HashMap<String, String> {
         let mut result = HashMap::new();
         for r#type in types {
@@ -338,105 +994,774 @@ impl ChatRoomInner {
         result
     }
 
-    fn send_message(&mut self, sender_id: usize, r#type: String, text: String) {
-        if let Some(sender) = self.participant_by_id(sender_id) {
-            if sender.username.is_none() || sender.display.is_none() {
-                return;
+    fn send_message(
+        &mut self,
+        sender_id: usize,
+        r#type: String,
+        text: String,
+        reply_to: Option<u64>,
+        transaction: Option<String>,
+    ) -> Option<TextRoomResponse> {
+        let participant = self.participant_by_id(sender_id)?;
+        let (Some(username), Some(display)) =
+            (participant.username.clone(), participant.display.clone())
+        else {
+            // anonymous participants can only read
+            return Some(error_response(transaction, ServiceError::Forbidden));
+        };
+        let slow_mode = self.room.slow_mode;
+        if let Some(client) = self.clients.get_mut(&sender_id).filter(|_| slow_mode > 0) {
+            if client
+                .last_message
+                .is_some_and(|e| e.elapsed().as_secs() < slow_mode)
+            {
+                return Some(TextRoomResponse::slow_mode(transaction));
             }
-            let now = Utc::now();
-            let username = sender.username.as_ref().unwrap();
-            let display = sender.display.as_ref().unwrap();
+            client.last_message = Some(Instant::now());
+        }
+        let thread = match reply_to {
+            None => None,
+            Some(parent) => match self.history_index(parent) {
+                None => return Some(error_response(transaction, ServiceError::MessageNotFound)),
+                Some(index) => Some(self.history[index].thread.unwrap_or(parent)),
+            },
+        };
+        let message = ChatMessage {
+            id: self.next_message_id(),
+            from: username,
+            display,
+            date: Utc::now(),
+            text,
+            r#type,
+            reply_to,
+            thread,
+            reactions: BTreeMap::new(),
+        };
 
-            self.broadcast_json(&TextRoomEvent::Message {
-                from: username,
-                display,
-                date: now,
-                text: &text,
-                r#type: &r#type,
-            });
+        self.broadcast_json(&TextRoomEvent::Message {
+            id: message.id,
+            from: &message.from,
+            display: &message.display,
+            date: message.date,
+            text: &message.text,
+            r#type: &message.r#type,
+            reply_to: message.reply_to,
+        });
 
-            self.post(
-                &Message {
-                    room: &self.room_name,
-                    textroom: Message::MESSAGE,
-                    r#type: &r#type,
-                    text: &text,
-                    date: now,
-                    from: &username,
-                },
-                true,
-            );
+        self.post(
+            &Message {
+                room: &self.room_name,
+                textroom: Message::MESSAGE,
+                id: Some(message.id),
+                reply_to: message.reply_to,
+                r#type: &message.r#type,
+                text: &message.text,
+                date: message.date,
+                from: &message.from,
+            },
+            true,
+        );
 
-            self.messages += 1;
-        }
+        self.remember(message);
+        None
     }
-    fn announce(&mut self, from_sender_id: usize, r#type: String, text: String) {
-        if let Some(sender) = self
-            .participant_by_id(from_sender_id)
-            .map(|e| e.username.as_deref())
-            .flatten()
-        {
-            let now = Utc::now();
-            self.broadcast_json(&TextRoomEvent::Announcement {
-                date: now,
-                text: &text,
-                r#type: &r#type,
-            });
-
-            self.post(
-                &Message {
-                    room: &self.room_name,
-                    textroom: Message::ANNOUNCEMENT,
-                    r#type: &r#type,
-                    text: &text,
-                    date: now,
-                    from: sender,
-                },
-                true,
-            );
 
-            self.messages += 1;
-            self.last_announcements.insert(r#type, text);
+    fn next_message_id(&self) -> u64 {
+        match &self.replication {
+            None => self.last_message_id + 1,
+            Some(replication) => replication.message_id(self.last_message_id),
         }
     }
 
-    fn ban(&mut self, sender_id: usize, victim: String) {
-        if let Some(from) = self
-            .participant_by_id(sender_id)
-            .map(|e| e.username.as_deref())
-            .flatten()
-        {
-            log!("{from} wants to ban {victim}");
+    /// Keeps a message sent on this instance or on another one in the history.
+    fn remember(&mut self, message: ChatMessage) {
+        let index = match self.history.binary_search_by_key(&message.id, |e| e.id) {
+            Ok(_) => return,
+            Err(index) => index,
+        };
+        self.messages += 1;
+        self.last_message_id = self.last_message_id.max(message.id);
+        self.history.insert(index, message);
+        if self.history.len() > HISTORY_SIZE {
+            self.history.pop_front();
+        }
+    }
 
-            self.post(
-                &Message {
-                    textroom: Message::MODERATE,
-                    room: &self.room_name,
-                    r#type: Message::TYPE_BAN.into(),
-                    text: &victim,
-                    date: Utc::now(),
-                    from,
-                },
-                false,
-            );
-            let victims: Vec<usize> = self
-                .clients
-                .iter()
-                .filter(|(_, client)| client.me.username.eq_to_some(&victim))
-                .map(|(id, _)| id.to_owned())
-                .collect();
-            let event = serde_json::to_string(&TextRoomEvent::Banned).unwrap();
-            for id in victims {
-                if let Some(client) = self.leave(id) {
-                    client.op.spawn().Send(WsMessage::Text(event.clone()));
+    /// Mirrors in the history what another instance did to a message.
+    fn on_replicated_event(&mut self, event: &serde_json::Value) {
+        let Ok(event) = TextRoomEvent::deserialize(event) else {
+            return;
+        };
+        match event {
+            TextRoomEvent::Announcement { r#type, text, .. } => {
+                self.last_announcements
+                    .insert(r#type.to_string(), text.to_string());
+            }
+            TextRoomEvent::Message {
+                id,
+                from,
+                display,
+                date,
+                text,
+                r#type,
+                reply_to,
+            } => {
+                let thread = reply_to.and_then(|parent| {
+                    let index = self.history_index(parent)?;
+                    Some(self.history[index].thread.unwrap_or(parent))
+                });
+                self.remember(ChatMessage {
+                    id,
+                    from: from.to_string(),
+                    display: display.to_string(),
+                    date,
+                    text: text.to_string(),
+                    r#type: r#type.to_string(),
+                    reply_to,
+                    thread,
+                    reactions: BTreeMap::new(),
+                });
+            }
+            TextRoomEvent::Reaction {
+                id, emoji, from, ..
+            } => {
+                if let Some(index) = self.history_index(id) {
+                    let reactions = &mut self.history[index].reactions;
+                    let users = reactions.entry(emoji.to_string()).or_default();
+                    // the event is only sent when the reaction was added or removed
+                    if !users.remove(from) {
+                        users.insert(from.to_string());
+                    }
+                    if users.is_empty() {
+                        reactions.remove(emoji);
+                    }
                 }
             }
+            _ => {}
         }
     }
 
-    fn post_created(&self) {
-        self.post(&Message::room_created(&self.room_name), false);
-    }
+    fn announce(&mut self, from_sender_id: usize, r#type: String, text: String) {
+        if let Some(sender) = self
+            .participant_by_id(from_sender_id)
+            .and_then(|e| e.username.clone())
+        {
+            self.announce_as(&sender, r#type, text);
+        }
+    }
+
+    fn announce_as(&mut self, sender: &str, r#type: String, text: String) {
+        let now = Utc::now();
+        self.broadcast_json(&TextRoomEvent::Announcement {
+            date: now,
+            text: &text,
+            r#type: &r#type,
+        });
+
+        self.post(
+            &Message {
+                room: &self.room_name,
+                textroom: Message::ANNOUNCEMENT,
+                id: None,
+                reply_to: None,
+                r#type: &r#type,
+                text: &text,
+                date: now,
+                from: sender,
+            },
+            true,
+        );
+
+        self.messages += 1;
+        self.last_announcements.insert(r#type, text);
+    }
+
+    fn react(
+        &mut self,
+        sender_id: usize,
+        id: u64,
+        emoji: String,
+        remove: bool,
+        transaction: Option<String>,
+    ) -> Option<TextRoomResponse> {
+        let Some(username) = self.participant_by_id(sender_id)?.username.clone() else {
+            return Some(error_response(transaction, ServiceError::Forbidden));
+        };
+        if emoji.is_empty() || emoji.len() > REACTION_MAX_LEN {
+            return Some(error_response(
+                transaction,
+                ServiceError::InvalidArgument("emoji"),
+            ));
+        }
+        let Some(index) = self.history_index(id) else {
+            return Some(error_response(transaction, ServiceError::MessageNotFound));
+        };
+        let reactions = &mut self.history[index].reactions;
+        let users = reactions.entry(emoji.clone()).or_default();
+        let changed = if remove {
+            users.remove(&username)
+        } else {
+            users.insert(username.clone())
+        };
+        let count = users.len();
+        if count == 0 {
+            reactions.remove(&emoji);
+        }
+        if changed {
+            self.broadcast_json(&TextRoomEvent::Reaction {
+                id,
+                emoji: &emoji,
+                from: &username,
+                count,
+            });
+        }
+        None
+    }
+
+    fn pin(&mut self, id: u64, expires_in: Option<u64>) -> Result<(), ServiceError> {
+        let index = self
+            .history_index(id)
+            .ok_or(ServiceError::MessageNotFound)?;
+        if expires_in.is_some_and(|e| e > PIN_MAX_DURATION.as_secs()) {
+            return Err(ServiceError::LimitExceeded("expiresIn"));
+        }
+        let now = Utc::now();
+        // pinning again only updates the expiry
+        self.pinned.retain(|e| e.message.id != id);
+        if self.pinned.len() == PINNED_MAX {
+            let oldest = self.pinned.remove(0);
+            self.broadcast_json(&TextRoomEvent::Unpinned {
+                id: oldest.message.id,
+            });
+        }
+        let pinned = PinnedMessage {
+            message: self.history[index].clone(),
+            pinned_at: now,
+            expires_at: expires_in.map(|e| now + Duration::seconds(e as i64)),
+        };
+        self.broadcast_json(&Self::pinned_event(&pinned));
+        self.pinned.push(pinned);
+        Ok(())
+    }
+
+    fn unpin(&mut self, id: u64) -> Result<(), ServiceError> {
+        let index = self
+            .pinned
+            .iter()
+            .position(|e| e.message.id == id)
+            .ok_or(ServiceError::MessageNotFound)?;
+        self.pinned.remove(index);
+        self.broadcast_json(&TextRoomEvent::Unpinned { id });
+        Ok(())
+    }
+
+    fn expire_pins(&mut self) {
+        let now = Utc::now();
+        let expired: Vec<u64> = self
+            .pinned
+            .iter()
+            .filter(|e| e.expires_at.is_some_and(|expires_at| expires_at <= now))
+            .map(|e| e.message.id)
+            .collect();
+        for id in expired {
+            let _ = self.unpin(id);
+        }
+    }
+
+    fn pinned_event(pinned: &PinnedMessage) -> TextRoomEvent<'_> {
+        let message = &pinned.message;
+        TextRoomEvent::Pinned {
+            id: message.id,
+            from: &message.from,
+            display: &message.display,
+            date: message.date,
+            text: &message.text,
+            r#type: &message.r#type,
+            expires_at: pinned.expires_at,
+        }
+    }
+
+    fn open_poll(
+        &mut self,
+        question: String,
+        options: Vec<String>,
+        duration: Option<u64>,
+        multiple: bool,
+        mut answers: Vec<usize>,
+    ) -> Result<Poll, ServiceError> {
+        if question.is_empty() {
+            return Err(ServiceError::InvalidArgument("question"));
+        }
+        if options.len() < 2 {
+            return Err(ServiceError::InvalidArgument("options"));
+        }
+        if options.len() > POLL_MAX_OPTIONS {
+            return Err(ServiceError::LimitExceeded("options"));
+        }
+        answers.sort_unstable();
+        answers.dedup();
+        if answers.iter().any(|e| *e >= options.len()) || (!multiple && answers.len() > 1) {
+            return Err(ServiceError::InvalidArgument("answers"));
+        }
+        let duration = duration.unwrap_or(POLL_DEFAULT_DURATION.as_secs());
+        if duration == 0 {
+            return Err(ServiceError::InvalidArgument("duration"));
+        }
+        if duration > POLL_MAX_DURATION.as_secs() {
+            return Err(ServiceError::LimitExceeded("duration"));
+        }
+        self.next_poll_id += 1;
+        let poll = Poll {
+            id: self.next_poll_id,
+            question,
+            votes: vec![0; options.len()],
+            options,
+            multiple,
+            ballots: HashMap::new(),
+            closes_at: Utc::now() + Duration::seconds(duration as i64),
+            closed: false,
+            answers,
+            correct: None,
+            winners: None,
+            is_dirty: false,
+        };
+        self.broadcast_json(&Self::poll_event(&poll));
+        self.polls.push(poll.clone());
+        Ok(poll)
+    }
+
+    fn vote(
+        &mut self,
+        sender_id: usize,
+        poll: u64,
+        mut options: Vec<usize>,
+        transaction: Option<String>,
+    ) -> Option<TextRoomResponse> {
+        let Some(username) = self.participant_by_id(sender_id)?.username.clone() else {
+            return Some(error_response(transaction, ServiceError::Forbidden));
+        };
+        let Some(poll) = self.polls.iter_mut().find(|e| e.id == poll && !e.closed) else {
+            return Some(error_response(transaction, ServiceError::PollNotFound));
+        };
+        if poll.ballots.contains_key(&username) {
+            return Some(TextRoomResponse::already_voted(transaction));
+        }
+        options.sort_unstable();
+        options.dedup();
+        if options.is_empty()
+            || (!poll.multiple && options.len() > 1)
+            || options.iter().any(|e| *e >= poll.options.len())
+        {
+            return Some(error_response(
+                transaction,
+                ServiceError::InvalidArgument("options"),
+            ));
+        }
+        for option in &options {
+            poll.votes[*option] += 1;
+        }
+        poll.ballots.insert(username, options);
+        poll.is_dirty = true;
+        None
+    }
+
+    fn close_poll(&mut self, id: u64) -> Result<(), ServiceError> {
+        let index = self
+            .polls
+            .iter()
+            .position(|e| e.id == id && !e.closed)
+            .ok_or(ServiceError::PollNotFound)?;
+        let poll = &mut self.polls[index];
+        poll.closed = true;
+        poll.is_dirty = false;
+        if !poll.answers.is_empty() {
+            let mut winners: Vec<String> = poll
+                .ballots
+                .iter()
+                .filter(|(_, options)| **options == poll.answers)
+                .map(|(username, _)| username.clone())
+                .collect();
+            winners.sort_unstable();
+            poll.winners = Some(winners);
+            poll.correct = Some(poll.answers.clone());
+        }
+
+        let event = TextRoomEvent::Results {
+            poll: poll.id,
+            votes: poll.votes.clone(),
+            voters: poll.ballots.len(),
+            correct: poll.correct.clone(),
+        };
+        self.broadcast_json(&event);
+        let poll = &self.polls[index];
+        if let Some(rest_client) = &self.rest_client {
+            rest_client.spawn_post(&PollResults {
+                textroom: Message::POLL,
+                room: &self.room_name,
+                poll,
+            });
+        }
+        if self.polls.iter().filter(|e| e.closed).count() > POLL_MAX_CLOSED {
+            let oldest = self.polls.iter().position(|e| e.closed).unwrap();
+            self.polls.remove(oldest);
+        }
+        Ok(())
+    }
+
+    /// Broadcasts the tally of polls that received votes and closes the expired ones.
+    fn flush_polls(&mut self) {
+        let now = Utc::now();
+        let mut expired = Vec::new();
+        let mut tallies = Vec::new();
+        for poll in self.polls.iter_mut().filter(|e| !e.closed) {
+            if poll.closes_at <= now {
+                expired.push(poll.id);
+            } else if poll.is_dirty {
+                poll.is_dirty = false;
+                tallies.push(TextRoomEvent::Tally {
+                    poll: poll.id,
+                    votes: poll.votes.clone(),
+                    voters: poll.ballots.len(),
+                });
+            }
+        }
+        for tally in tallies {
+            self.broadcast_json(&tally);
+        }
+        for id in expired {
+            let _ = self.close_poll(id);
+        }
+    }
+
+    fn poll_event(poll: &Poll) -> TextRoomEvent<'_> {
+        TextRoomEvent::Poll {
+            id: poll.id,
+            question: &poll.question,
+            options: poll.options.iter().map(|e| e.as_str()).collect(),
+            multiple: poll.multiple,
+            closes_at: poll.closes_at,
+        }
+    }
+
+    fn read(
+        &mut self,
+        sender_id: usize,
+        id: u64,
+        transaction: Option<String>,
+    ) -> Option<TextRoomResponse> {
+        let Some(username) = self.participant_by_id(sender_id)?.username.clone() else {
+            return Some(error_response(transaction, ServiceError::Forbidden));
+        };
+        if id > self.last_message_id {
+            return Some(error_response(transaction, ServiceError::MessageNotFound));
+        }
+        let marker = self.read_markers.entry(username.clone()).or_default();
+        // markers only move forward
+        if id <= *marker {
+            return None;
+        }
+        *marker = id;
+        if self.count() <= READ_RECEIPTS_MAX_PARTICIPANTS {
+            self.broadcast_json(&TextRoomEvent::Read {
+                username: &username,
+                id,
+            });
+        }
+        None
+    }
+
+    fn read_markers(&self, username: Option<String>) -> HashMap<String, u64> {
+        match username {
+            None => self.read_markers.clone(),
+            Some(username) => self
+                .read_markers
+                .get_key_value(&username)
+                .map(|(k, v)| (k.clone(), *v))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn burst(
+        &mut self,
+        emoji: String,
+        count: Option<usize>,
+        transaction: Option<String>,
+    ) -> Option<TextRoomResponse> {
+        if emoji.is_empty() || emoji.len() > REACTION_MAX_LEN {
+            return Some(error_response(
+                transaction,
+                ServiceError::InvalidArgument("emoji"),
+            ));
+        }
+        if self.bursts.len() >= BURST_MAX_KINDS && !self.bursts.contains_key(&emoji) {
+            return None;
+        }
+        let count = count.unwrap_or(1).min(BURST_MAX_COUNT);
+        *self.bursts.entry(emoji).or_default() += count;
+        None
+    }
+
+    /// Sends the taps aggregated since the last tick as a single `bursts` event.
+    fn flush_bursts(&mut self) {
+        if self.bursts.is_empty() {
+            return;
+        }
+        let bursts = std::mem::take(&mut self.bursts);
+        let counts = bursts.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        self.broadcast_json(&TextRoomEvent::Bursts { counts });
+    }
+
+    fn typing(&mut self, sender_id: usize) {
+        if let Some(client) = self.clients.get_mut(&sender_id) {
+            if client.me.username.is_none() || client.me.display.is_none() {
+                return;
+            }
+            let now = Instant::now();
+            if client
+                .last_typing
+                .is_some_and(|last| now - last < TYPING_THROTTLE)
+            {
+                return;
+            }
+            client.last_typing = Some(now);
+            self.typing.push(sender_id);
+        }
+    }
+
+    /// Sends a single `typing` event for everyone who started typing since the last tick.
+    fn flush_typing(&mut self) {
+        let typers: Vec<usize> = std::mem::take(&mut self.typing)
+            .into_iter()
+            .filter(|id| self.clients.contains_key(id))
+            .collect();
+        if typers.is_empty() {
+            return;
+        }
+        let listed = &typers[..typers.len().min(TYPING_MAX_USERS)];
+        let others = typers.len() - listed.len();
+
+        let content = shared_json(&self.typing_event(listed, None, others));
+        for (id, client) in &self.clients {
+            if !listed.contains(id) {
+                client.send_droppable(content.clone())
+            }
+        }
+        // a listed typer should not be told about their own typing
+        if listed.len() > 1 || others > 0 {
+            for id in listed {
+                self.reply_json(*id, &self.typing_event(listed, Some(*id), others));
+            }
+        }
+    }
+
+    fn typing_event(
+        &self,
+        listed: &[usize],
+        except: Option<usize>,
+        others: usize,
+    ) -> TextRoomEvent<'_> {
+        let users = listed
+            .iter()
+            .filter(|id| except != Some(**id))
+            .filter_map(|id| self.participant_by_id(*id))
+            .map(|e| Typer {
+                username: e.username.or_empty(),
+                display: e.display.or_empty(),
+            })
+            .collect();
+        TextRoomEvent::Typing { users, others }
+    }
+
+    fn ban(&mut self, sender_id: usize, victim: String) {
+        if let Some(from) = self
+            .participant_by_id(sender_id)
+            .and_then(|e| e.username.as_deref())
+        {
+            log!("{from} wants to ban {victim}");
+
+            self.post(
+                &Message {
+                    textroom: Message::MODERATE,
+                    id: None,
+                    reply_to: None,
+                    room: &self.room_name,
+                    r#type: Message::TYPE_BAN,
+                    text: &victim,
+                    date: Utc::now(),
+                    from,
+                },
+                false,
+            );
+            self.kick(victim.clone());
+            self.replicate(&RoomSync::Ban { username: victim });
+        }
+    }
+
+    /// Disconnects the clients of `victim` and forgets their sessions.
+    fn kick(&mut self, victim: String) {
+        let victims: Vec<usize> = self
+            .clients
+            .iter()
+            .chain(&self.viewers)
+            .filter(|(_, client)| client.me.username.eq_to_some(&victim))
+            .map(|(id, _)| id.to_owned())
+            .collect();
+        let event = shared_json(&TextRoomEvent::Banned);
+        for id in victims {
+            if let Some(client) = self.leave(id) {
+                client.send(event.clone());
+            }
+        }
+        let sessions: Vec<String> = self
+            .suspended
+            .iter()
+            .filter(|(_, e)| e.me.username.eq_to_some(&victim))
+            .map(|(token, _)| token.clone())
+            .collect();
+        for token in sessions {
+            self.drop_session(&token);
+        }
+    }
+
+    fn on_tick(&mut self) {
+        self.flush_typing();
+        self.flush_presence();
+        self.flush_bursts();
+        self.expire_pins();
+        self.flush_polls();
+        self.expire_sessions();
+        self.publish_presence();
+        self.publish_refresh();
+        self.expire_room();
+    }
+
+    /// Destroys the room once past its expiry or empty for too long, as a `destroy` request
+    /// would. Replicas wait for the instance that created the room to do it, unless it stopped
+    /// refreshing them.
+    fn expire_room(&mut self) {
+        if self.is_replica {
+            if self.origin_deadline <= Instant::now() {
+                log!("replica of room `{}` expired", self.room_name);
+                self.destroy();
+            }
+            return;
+        }
+        if self.count() > 0 {
+            self.idle_since = None;
+        } else if self.idle_since.is_none() {
+            self.idle_since = Some(Instant::now());
+        }
+        let is_expired = self.room.expires_at.is_some_and(|e| e <= Utc::now());
+        let is_idle = match (self.room.idle_timeout, self.idle_since) {
+            (Some(timeout), Some(since)) => since.elapsed().as_secs() >= timeout,
+            _ => false,
+        };
+        if is_expired || is_idle {
+            log!("room `{}` expired", self.room_name);
+            self.destroy();
+        }
+    }
+
+    fn on_sync(&mut self, sync: RoomSync) {
+        match sync {
+            RoomSync::Event { event } => {
+                self.on_replicated_event(&event);
+                self.broadcast_local(&event, false);
+            }
+            RoomSync::Presence {
+                instance,
+                participants,
+            } => {
+                if participants.is_empty() {
+                    self.remote.remove(&instance);
+                } else {
+                    let deadline = Instant::now() + PRESENCE_TTL;
+                    self.remote.insert(
+                        instance,
+                        RemotePresence {
+                            participants,
+                            deadline,
+                        },
+                    );
+                }
+            }
+            RoomSync::PresenceChanged {
+                instance,
+                joined,
+                left,
+            } => {
+                let remote = self
+                    .remote
+                    .entry(instance.clone())
+                    .or_insert(RemotePresence {
+                        participants: Vec::new(),
+                        deadline: Instant::now(),
+                    });
+                for participant in left {
+                    if let Some(index) = remote.participants.iter().position(|e| *e == participant)
+                    {
+                        remote.participants.remove(index);
+                    }
+                }
+                remote.participants.extend(joined);
+                remote.deadline = Instant::now() + PRESENCE_TTL;
+                if remote.participants.is_empty() {
+                    self.remote.remove(&instance);
+                }
+            }
+            RoomSync::Ban { username } => self.kick(username),
+            RoomSync::Updated { update } => self.apply_update(update),
+            RoomSync::Refresh => self.origin_deadline = Instant::now() + PRESENCE_TTL,
+        }
+    }
+
+    /// Tells the other instances who joined or left here, and who is connected before they
+    /// forget. The full list is only sent once per [PRESENCE_REFRESH], or when there were too
+    /// many changes to list.
+    fn publish_presence(&mut self) {
+        let now = Instant::now();
+        self.remote.retain(|_, e| e.deadline > now);
+        let Some(replication) = &self.replication else {
+            return;
+        };
+        let delta = std::mem::take(&mut self.unpublished_presence);
+        let instance = replication.origin().to_string();
+        let sync = if delta.others > 0 || now >= self.next_presence {
+            let participants: Vec<Participant> = self.local_participants().cloned().collect();
+            if participants.is_empty() && delta.is_empty() {
+                return;
+            }
+            self.next_presence = now + PRESENCE_REFRESH;
+            RoomSync::Presence {
+                instance,
+                participants,
+            }
+        } else if !delta.is_empty() {
+            RoomSync::PresenceChanged {
+                instance,
+                joined: delta.joined,
+                left: delta.left,
+            }
+        } else {
+            return;
+        };
+        replication.publish(&room_channel(&self.room.uid), &sync);
+    }
+
+    /// Tells the replicas that the room still exists, once per [PRESENCE_REFRESH].
+    fn publish_refresh(&mut self) {
+        let now = Instant::now();
+        if self.is_replica || now < self.next_refresh {
+            return;
+        }
+        self.next_refresh = now + PRESENCE_REFRESH;
+        self.replicate(&RoomSync::Refresh);
+    }
+
+    fn post_created(&self) {
+        self.post(&Message::room_created(&self.room_name), false);
+    }
 
     fn post(&self, message: &Message, should_check_type: bool) {
         if let Some(rest_client) = &self.rest_client {
@@ -453,23 +1778,46 @@ impl ChatRoomInner {
         }
     }
 
-    fn broadcast_json<T: Serialize>(&self, body: &T) {
-        let content = serde_json::to_string(body).unwrap();
-        self.broadcast(content)
+    /// Sends an event to every client, including the ones of other instances.
+    fn broadcast_json(&mut self, event: &TextRoomEvent) {
+        let critical = event.is_critical();
+        self.broadcast_local(event, critical);
+        if !critical {
+            self.replicate(&RoomSync::Event {
+                event: serde_json::to_value(event).unwrap(),
+            });
+        }
+    }
+
+    /// Sends an event to the clients of this instance, numbered and kept for replays.
+    fn broadcast_local<T: Serialize>(&mut self, event: &T, critical: bool) {
+        self.seq += 1;
+        let content = shared_json(&Sequenced {
+            seq: self.seq,
+            event,
+        });
+        for client in self.clients.values().chain(self.viewers.values()) {
+            if critical {
+                client.send(content.clone())
+            } else {
+                client.send_droppable(content.clone())
+            }
+        }
+        if self.events.len() == EVENT_LOG_SIZE {
+            self.events.pop_front();
+        }
+        self.events.push_back((self.seq, content));
     }
 
-    fn broadcast(&self, body: String) {
-        for (_, client) in &self.clients {
-            client.op.spawn().Send(WsMessage::Text(body.clone()))
+    fn replicate(&self, sync: &RoomSync) {
+        if let Some(replication) = &self.replication {
+            replication.publish(&room_channel(&self.room.uid), sync);
         }
     }
 
     fn reply_json<T: Serialize>(&self, receiver_id: usize, body: &T) {
-        if let Some(client) = self.clients.get(&receiver_id) {
-            client
-                .op
-                .spawn()
-                .Send(WsMessage::Text(serde_json::to_string(body).unwrap()));
+        if let Some(client) = self.client(receiver_id) {
+            client.send(shared_json(body));
         }
     }
     fn destroy(&mut self) {
@@ -477,13 +1825,898 @@ impl ChatRoomInner {
             self.is_destroyed = true;
             self.broadcast_json(&TextRoomEvent::Destroyed);
             self.clients.clear();
+            self.viewers.clear();
+            self.suspended.clear();
             log!("room `{}` destroyed", self.room_name);
-            self.post(&Message::room_destroyed(&self.room_name), false)
+            if !self.is_replica {
+                self.post(&Message::room_destroyed(&self.room_name), false)
+            }
+        }
+    }
+}
+
+struct Suspended {
+    me: Participant,
+    deadline: Instant,
+}
+
+struct RemotePresence {
+    participants: Vec<Participant>,
+    deadline: Instant,
+}
+
+#[derive(Default)]
+struct PresenceDelta {
+    joined: Vec<Participant>,
+    left: Vec<Participant>,
+    others: usize,
+}
+
+impl PresenceDelta {
+    fn push(&mut self, participant: &Participant, joined: bool) {
+        let (list, opposite) = if joined {
+            (&mut self.joined, &mut self.left)
+        } else {
+            (&mut self.left, &mut self.joined)
+        };
+        // left and came back, or the other way around, since the last tick
+        if let Some(index) = opposite.iter().position(|e| e == participant) {
+            opposite.swap_remove(index);
+        } else if list.len() + opposite.len() < PRESENCE_DELTA_MAX {
+            list.push(participant.clone());
+        } else {
+            self.others += 1;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.joined.is_empty() && self.left.is_empty() && self.others == 0
+    }
+}
+
+/// `from` of the announcements made through the admin endpoints.
+const ADMIN_SENDER: &str = "admin";
+
+/// Waits forever without a backplane.
+async fn next_sync(subscription: &mut Option<Subscription<RoomSync>>) -> Option<RoomSync> {
+    match subscription {
+        Some(subscription) => subscription.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Same code and message as the REST actions, except for a room gone under a connected client.
+fn error_response(transaction: Option<String>, error: ServiceError) -> TextRoomResponse {
+    match error {
+        ServiceError::RoomNotFound | ServiceError::ActorGone => {
+            TextRoomResponse::destroyed(transaction)
         }
+        error => TextRoomResponse::error(transaction, error.code(), error.message()),
     }
 }
 
+/// The webhook of the room, or else the default one of its namespace.
+fn rest_client(room: &Room, namespace: &Namespace) -> Option<RestClient> {
+    let post = room.post.as_ref().or(namespace.webhook.as_ref())?;
+    Some(RestClient::create(
+        post.clone(),
+        namespace.signing_keys.clone(),
+    ))
+}
+
+/// Serializes once into a buffer every recipient can share.
+fn shared_json<T: Serialize>(body: &T) -> Utf8Bytes {
+    serde_json::to_string(body).unwrap().into()
+}
+
 #[derive(Deserialize)]
 struct UnknownTextRoomRequest {
     transaction: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::mpsc::UnboundedReceiver;
+    use futures::SinkExt;
+
+    use super::*;
+    use crate::config::CLIENT_QUEUE_SIZE;
+    use crate::service::backplane::{room_channel, InProcessBackplane};
+    use crate::service::Backplane;
+
+    fn participant(username: &str, display: &str) -> Participant {
+        Participant {
+            username: Some(username.to_string()),
+            display: Some(display.to_string()),
+        }
+    }
+
+    /// A socket ignoring what the room sends.
+    fn sink() -> WebSocketSink {
+        Box::pin(futures::sink::drain().sink_map_err(|e| match e {}))
+    }
+
+    /// A participant without a username, who can only read.
+    fn anonymous_params() -> JoinParams {
+        JoinParams {
+            username: None,
+            ..join_params("anonymous")
+        }
+    }
+
+    /// A socket keeping what the room sends, until the client is dropped.
+    fn recorder() -> (WebSocketSink, UnboundedReceiver<WsMessage>) {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let sink = tx.sink_map_err(|_| tokio_tungstenite::tungstenite::Error::ConnectionClosed);
+        (Box::pin(sink), rx)
+    }
+
+    /// The JSON texts received by a recorder.
+    async fn received(rx: UnboundedReceiver<WsMessage>) -> Vec<serde_json::Value> {
+        rx.filter_map(|e| async move {
+            match e {
+                WsMessage::Text(text) => Some(serde_json::from_str(&text).unwrap()),
+                _ => None,
+            }
+        })
+        .collect()
+        .await
+    }
+
+    /// The `join` and `leave` events received by a recorder.
+    async fn received_presence(rx: UnboundedReceiver<WsMessage>) -> Vec<serde_json::Value> {
+        received(rx)
+            .await
+            .into_iter()
+            .filter(|e| e["textroom"] == "join" || e["textroom"] == "leave")
+            .collect()
+    }
+
+    /// The room events of type `textroom` kept for replays.
+    fn logged(state: &ChatRoomInner, textroom: &str) -> Vec<serde_json::Value> {
+        state
+            .events
+            .iter()
+            .map(|(_, e)| serde_json::from_str::<serde_json::Value>(e).unwrap())
+            .filter(|e| e["textroom"] == textroom)
+            .collect()
+    }
+
+    fn hello() -> String {
+        serde_json::json!({"textroom": "message", "type": "text", "text": "hello"}).to_string()
+    }
+
+    /// The `seq` of each event, replies and the welcome excluded.
+    fn seqs(received: &[serde_json::Value]) -> Vec<u64> {
+        received
+            .iter()
+            .filter(|e| e["textroom"] != "welcome")
+            .filter_map(|e| e["seq"].as_u64())
+            .collect()
+    }
+
+    fn join_params(username: &str) -> JoinParams {
+        JoinParams {
+            username: Some(username.to_string()),
+            display: Some(username.to_uppercase()),
+            image_url: None,
+            resume: None,
+            last: None,
+        }
+    }
+
+    /// The state of a `/app/room` room, without its actor.
+    fn state(replication: Option<Replication>) -> ChatRoomInner {
+        let room = Room::new("/app/room", "secret");
+        let secret = Arc::new(RwLock::new(room.secret.clone()));
+        ChatRoomInner::new(room, Namespace::default(), secret, replication, false)
+    }
+
+    async fn join(chat_room: &ChatRoom, username: &str) -> usize {
+        chat_room
+            .op
+            .Join(sink(), join_params(username))
+            .await
+            .unwrap()
+    }
+
+    async fn send(chat_room: &ChatRoom, id: usize, request: serde_json::Value) {
+        let message = WsMessage::text(request.to_string());
+        chat_room.op.OnMessageReceived(id, message).await.unwrap();
+    }
+
+    /// The history of `chat_room` once it has `count` messages.
+    async fn wait_history(chat_room: &ChatRoom, count: usize) -> Vec<ChatMessage> {
+        for _ in 0..200 {
+            let history = chat_room.op.History(None).await.unwrap();
+            if history.len() == count {
+                return history;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("timed out");
+    }
+
+    #[tokio::test]
+    async fn test_replicated_history() {
+        let backplane: Arc<dyn Backplane> = Arc::new(InProcessBackplane::default());
+        let (on_exit, _) = mpsc::unbounded_channel();
+        let [origin, replica] = [false, true].map(|is_replica| {
+            let replication = Replication::new(backplane.clone());
+            let room = Room::new("/app/room", "secret");
+            ChatRoom::create(
+                room,
+                Namespace::default(),
+                Some(replication),
+                is_replica,
+                on_exit.clone(),
+            )
+        });
+        let alice = join(&origin, "alice").await;
+        let bob = join(&replica, "bob").await;
+
+        let hello = serde_json::json!({"textroom": "message", "type": "text", "text": "hello"});
+        send(&origin, alice, hello).await;
+        let id = wait_history(&replica, 1).await[0].id;
+        let reaction = serde_json::json!({"textroom": "react", "id": id, "emoji": "+1"});
+        send(&replica, bob, reaction).await;
+        let reply = serde_json::json!({
+            "textroom": "message",
+            "type": "text",
+            "text": "hi",
+            "replyTo": id,
+        });
+        send(&replica, bob, reply).await;
+
+        let history = wait_history(&origin, 2).await;
+        assert_eq!(history[0].id, id);
+        assert!(history[1].id > id);
+        assert_eq!(history[1].from, "bob");
+        assert_eq!(history[1].thread, Some(id));
+        // events of the room channel arrive in order, the reaction before the reply
+        assert!(history[0].reactions["+1"].contains("bob"));
+    }
+
+    #[test]
+    fn test_presence_delta() {
+        let mut delta = PresenceDelta::default();
+        delta.push(&participant("a", "A"), true);
+        delta.push(&participant("b", "B"), false);
+        delta.push(&participant("a", "A"), false);
+        assert!(delta.joined.is_empty());
+        assert_eq!(delta.left, [participant("b", "B")]);
+
+        for i in 0..PRESENCE_DELTA_MAX {
+            delta.push(&participant(&i.to_string(), ""), true);
+        }
+        assert_eq!(delta.joined.len() + delta.left.len(), PRESENCE_DELTA_MAX);
+        assert_eq!(delta.others, 1);
+    }
+
+    #[tokio::test]
+    async fn test_presence_threshold() {
+        let mut state = state(None);
+        state.room.presence_threshold = Some(2);
+        state.join(sink(), join_params("a"));
+        let b = state.join(sink(), join_params("b"));
+        // b brought the room to the threshold
+        let joined = logged(&state, "join");
+        assert_eq!(joined.len(), 1);
+        assert_eq!(joined[0]["participants"], 1);
+        state.flush_presence();
+        let presence = logged(&state, "presence");
+        assert_eq!(presence[0]["joined"][0]["username"], "b");
+        assert_eq!(presence[0]["participants"], 2);
+
+        state.leave(b);
+        assert_eq!(logged(&state, "leave").len(), 1);
+    }
+
+    #[test]
+    fn test_pin_max_duration() {
+        let mut state = state(None);
+        state.history.push_back(ChatMessage {
+            id: 1,
+            from: "alice".to_string(),
+            display: "Alice".to_string(),
+            date: Utc::now(),
+            text: "hello".to_string(),
+            r#type: "text".to_string(),
+            reply_to: None,
+            thread: None,
+            reactions: BTreeMap::new(),
+        });
+
+        let max = PIN_MAX_DURATION.as_secs();
+        assert!(matches!(
+            state.pin(1, Some(u64::MAX)),
+            Err(ServiceError::LimitExceeded("expiresIn"))
+        ));
+        assert!(matches!(
+            state.pin(1, Some(max + 1)),
+            Err(ServiceError::LimitExceeded("expiresIn"))
+        ));
+        assert!(state.pinned.is_empty());
+        assert!(state.pin(1, Some(max)).is_ok());
+        assert_eq!(state.pinned.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_thread() {
+        let mut state = state(None);
+        let alice = state.join(sink(), join_params("alice"));
+        let message = |state: &mut ChatRoomInner, reply_to: Option<u64>| {
+            let response = state.send_message(
+                alice,
+                "text".to_string(),
+                "hello".to_string(),
+                reply_to,
+                Some("1".to_string()),
+            );
+            match response {
+                None => Ok(state.history.back().unwrap().id),
+                Some(response) => Err(serde_json::to_value(response).unwrap()["code"].clone()),
+            }
+        };
+        let root = message(&mut state, None).unwrap();
+        let first = message(&mut state, Some(root)).unwrap();
+        let nested = message(&mut state, Some(first)).unwrap();
+        let other = message(&mut state, None).unwrap();
+        assert_eq!(
+            message(&mut state, Some(other + 1)).unwrap_err(),
+            "message_not_found"
+        );
+
+        let ids = |thread: Option<Vec<ChatMessage>>| -> Vec<u64> {
+            thread.unwrap().iter().map(|e| e.id).collect()
+        };
+        let index = state.history_index(nested).unwrap();
+        assert_eq!(state.history[index].thread, Some(root));
+        assert_eq!(ids(state.thread(nested)), [root, first, nested]);
+        assert_eq!(ids(state.thread(root)), [root, first, nested]);
+        assert_eq!(ids(state.thread(other)), [other]);
+        assert!(state.thread(other + 1).is_none());
+
+        // out of the history
+        for _ in 0..HISTORY_SIZE {
+            message(&mut state, None).unwrap();
+        }
+        assert_eq!(
+            message(&mut state, Some(root)).unwrap_err(),
+            "message_not_found"
+        );
+        assert!(state.thread(root).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_react() {
+        let mut state = state(None);
+        let alice = state.join(sink(), join_params("alice"));
+        let anonymous = state.join(sink(), anonymous_params());
+        state.on_listen(alice, &hello());
+        let id = state.history[0].id;
+
+        assert!(state
+            .react(alice, id, "+1".to_string(), false, None)
+            .is_none());
+        assert!(state.history[0].reactions["+1"].contains("alice"));
+        let response = state.react(
+            anonymous,
+            id,
+            "+1".to_string(),
+            false,
+            Some("1".to_string()),
+        );
+        let response = serde_json::to_value(response).unwrap();
+        assert_eq!(response["code"], "forbidden");
+        assert_eq!(response["transaction"], "1");
+        assert_eq!(state.history[0].reactions["+1"].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_read() {
+        let mut state = state(None);
+        let alice = state.join(sink(), join_params("alice"));
+        let bob = state.join(sink(), join_params("bob"));
+        for _ in 0..3 {
+            state.on_listen(alice, &hello());
+        }
+        let ids: Vec<u64> = state.history.iter().map(|e| e.id).collect();
+
+        assert!(state.read(bob, ids[1], None).is_none());
+        let seq = state.seq;
+        // markers only move forward
+        assert!(state.read(bob, ids[0], None).is_none());
+        assert_eq!(state.read_markers["bob"], ids[1]);
+        assert_eq!(state.seq, seq);
+        let response = state.read(bob, ids[2] + 1, Some("1".to_string()));
+        let response = serde_json::to_value(response).unwrap();
+        assert_eq!(response["code"], "message_not_found");
+        assert_eq!(response["transaction"], "1");
+        assert_eq!(state.read_markers["bob"], ids[1]);
+
+        let anonymous = state.join(sink(), anonymous_params());
+        let response = state.read(anonymous, ids[2], Some("2".to_string()));
+        let response = serde_json::to_value(response).unwrap();
+        assert_eq!(response["code"], "forbidden");
+        assert_eq!(response["transaction"], "2");
+
+        state.leave(bob);
+        state.on_listen(alice, &hello());
+        let (socket, rx) = recorder();
+        state.join(socket, join_params("bob"));
+        drop(state);
+        let welcome = &received(rx).await[0];
+        assert_eq!(welcome["unread"]["lastRead"], ids[1]);
+        assert_eq!(welcome["unread"]["count"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_typing() {
+        // the events below list 3 of the 4 typers
+        assert_eq!(TYPING_MAX_USERS, 3);
+        let mut state = state(None);
+        let (ids, rxs): (Vec<usize>, Vec<_>) = ["alice", "bob", "carol", "dave", "erin"]
+            .iter()
+            .map(|username| {
+                let (socket, rx) = recorder();
+                (state.join(socket, join_params(username)), rx)
+            })
+            .unzip();
+        let [alice, bob, carol, dave, erin] = ids[..] else {
+            unreachable!()
+        };
+
+        state.typing(alice);
+        state.typing(alice);
+        assert_eq!(state.typing, [alice]);
+        state.flush_typing();
+        for id in [bob, carol, dave, erin] {
+            state.typing(id);
+        }
+        state.typing(alice);
+        assert_eq!(state.typing, [bob, carol, dave, erin]);
+        state.flush_typing();
+        state.flush_typing();
+        state.clients.get_mut(&alice).unwrap().last_typing = Some(Instant::now() - TYPING_THROTTLE);
+        state.typing(alice);
+        assert_eq!(state.typing, [alice]);
+        drop(state);
+
+        let mut received_typing = Vec::new();
+        for rx in rxs {
+            let typing: Vec<(Vec<String>, u64)> = received(rx)
+                .await
+                .iter()
+                .filter(|e| e["textroom"] == "typing")
+                .map(|e| {
+                    let users = e["users"].as_array().unwrap().iter();
+                    let users = users.map(|e| e["username"].as_str().unwrap().to_string());
+                    (users.collect(), e["others"].as_u64().unwrap())
+                })
+                .collect();
+            received_typing.push(typing);
+        }
+        let users = |users: &[&str]| users.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        // one event per tick, without the recipient
+        assert_eq!(received_typing[0], [(users(&["bob", "carol", "dave"]), 1)]);
+        assert_eq!(
+            received_typing[1],
+            [(users(&["alice"]), 0), (users(&["carol", "dave"]), 1)]
+        );
+        assert_eq!(
+            received_typing[4],
+            [
+                (users(&["alice"]), 0),
+                (users(&["bob", "carol", "dave"]), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_removes_settings() {
+        let mut state = state(None);
+        state.apply_update(RoomUpdate {
+            post: Some("https://example.com".to_string()),
+            expires_at: Some(Some(Utc::now())),
+            idle_timeout: Some(60),
+            max_participants: Some(10),
+            presence_threshold: Some(10),
+            ..Default::default()
+        });
+        assert!(state.rest_client.is_some());
+        assert_eq!(state.presence_threshold(), 10);
+
+        state.apply_update(RoomUpdate {
+            post: Some(String::new()),
+            expires_at: Some(None),
+            idle_timeout: Some(0),
+            max_participants: Some(0),
+            presence_threshold: Some(0),
+            ..Default::default()
+        });
+        assert!(state.rest_client.is_none());
+        assert_eq!(state.room.expires_at, None);
+        assert_eq!(state.room.idle_timeout, None);
+        assert_eq!(state.room.max_participants, None);
+        assert_eq!(state.presence_threshold(), PRESENCE_THRESHOLD);
+    }
+
+    #[test]
+    fn test_burst_max_kinds() {
+        let mut state = state(None);
+        for i in 0..=BURST_MAX_KINDS {
+            assert!(state.burst(i.to_string(), None, None).is_none());
+        }
+        assert!(state.burst("0".to_string(), Some(2), None).is_none());
+        assert_eq!(state.bursts.len(), BURST_MAX_KINDS);
+        assert_eq!(state.bursts["0"], 3);
+        assert!(!state.bursts.contains_key(&BURST_MAX_KINDS.to_string()));
+
+        state.flush_bursts();
+        assert!(state
+            .burst(BURST_MAX_KINDS.to_string(), None, None)
+            .is_none());
+        assert_eq!(state.bursts.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_quiz() {
+        let mut state = state(None);
+        let alice = state.join(sink(), join_params("alice"));
+        let alice_again = state.join(sink(), join_params("alice"));
+        let bob = state.join(sink(), join_params("bob"));
+        let anonymous = state.join(sink(), anonymous_params());
+        let options = ["a", "b", "c"].map(str::to_string).to_vec();
+        let poll = state
+            .open_poll("quiz?".to_string(), options, None, false, vec![1])
+            .unwrap()
+            .id;
+        let code = |response: Option<TextRoomResponse>| {
+            serde_json::to_value(response.unwrap()).unwrap()["code"].clone()
+        };
+
+        assert!(state.vote(alice, poll, vec![1], None).is_none());
+        // one vote per user, whatever the connection
+        let response = state.vote(alice_again, poll, vec![0], None);
+        assert_eq!(code(response), "already_voted");
+        assert!(state.vote(bob, poll, vec![0], None).is_none());
+        let response = state.vote(anonymous, poll, vec![1], Some("1".to_string()));
+        assert_eq!(code(response), "forbidden");
+        let response = state.vote(bob, poll + 1, vec![0], None);
+        assert_eq!(code(response), "poll_not_found");
+
+        // tallies wait for the next tick and are only sent after new votes
+        assert!(logged(&state, "tally").is_empty());
+        state.flush_polls();
+        state.flush_polls();
+        let tallies = logged(&state, "tally");
+        assert_eq!(tallies.len(), 1);
+        assert_eq!(tallies[0]["votes"], serde_json::json!([1, 1, 0]));
+        assert_eq!(tallies[0]["voters"], 2);
+
+        state.close_poll(poll).unwrap();
+        let results = &logged(&state, "results")[0];
+        assert_eq!(results["correct"], serde_json::json!([1]));
+        assert_eq!(state.polls[0].winners, Some(vec!["alice".to_string()]));
+        let response = state.vote(bob, poll, vec![1], None);
+        assert_eq!(code(response), "poll_not_found");
+    }
+
+    #[test]
+    fn test_poll_max_closed() {
+        let mut state = state(None);
+        let options = vec!["yes".to_string(), "no".to_string()];
+        let open = state
+            .open_poll(
+                "open?".to_string(),
+                options.clone(),
+                None,
+                false,
+                Vec::new(),
+            )
+            .unwrap();
+        for i in 0..=POLL_MAX_CLOSED {
+            let poll = state
+                .open_poll(i.to_string(), options.clone(), None, false, Vec::new())
+                .unwrap();
+            state.close_poll(poll.id).unwrap();
+        }
+        assert_eq!(state.polls.len(), POLL_MAX_CLOSED + 1);
+        assert_eq!(state.polls[0].id, open.id);
+        assert_eq!(state.polls[1].question, "1");
+    }
+
+    #[test]
+    fn test_can_replay() {
+        let mut state = state(None);
+        for _ in 0..EVENT_LOG_SIZE + 1 {
+            state.broadcast_json(&TextRoomEvent::Bursts {
+                counts: BTreeMap::new(),
+            });
+        }
+        assert!(!state.can_replay(0));
+        // kept, but too many for the queue of the client
+        assert!(!state.can_replay(1));
+        assert!(!state.can_replay(state.seq - REPLAY_MAX as u64 - 1));
+        assert!(state.can_replay(state.seq - REPLAY_MAX as u64));
+        assert!(state.can_replay(u64::MAX));
+    }
+
+    #[tokio::test]
+    async fn test_resume() {
+        let mut state = state(None);
+        let (socket, alice_rx) = recorder();
+        let alice = state.join(socket, join_params("alice"));
+        let bob = state.join(sink(), join_params("bob"));
+        let resume = state.clients[&bob].resume_token.clone();
+        state.suspend(bob);
+        assert_eq!(state.count(), 2);
+        let last = state.seq;
+        state.on_listen(alice, &hello());
+        state.on_listen(alice, &hello());
+
+        let (socket, bob_rx) = recorder();
+        let params = JoinParams {
+            resume: Some(resume),
+            last: Some(last),
+            ..join_params("someone")
+        };
+        let id = state.join(socket, params);
+        assert_eq!(state.clients[&id].me, participant("bob", "BOB"));
+        assert_eq!(state.count(), 2);
+        let replay = serde_json::json!({"textroom": "replay", "since": last});
+        state.on_listen(id, &replay.to_string());
+        drop(state);
+
+        let received = received(bob_rx).await;
+        assert_eq!(received[0]["textroom"], "welcome");
+        assert_eq!(received[0]["resumed"], true);
+        assert!(received[0].get("gap").is_none());
+        // once after the welcome, then for the `replay` action
+        assert_eq!(seqs(&received), [last + 1, last + 2, last + 1, last + 2]);
+        // bob never left
+        let presence: Vec<serde_json::Value> = received_presence(alice_rx).await;
+        assert_eq!(presence.len(), 1);
+        assert_eq!(presence[0]["textroom"], "join");
+        assert_eq!(presence[0]["username"], "bob");
+    }
+
+    #[tokio::test]
+    async fn test_resume_gap() {
+        let mut state = state(None);
+        let alice = state.join(sink(), join_params("alice"));
+        let bob = state.join(sink(), join_params("bob"));
+        let resume = state.clients[&bob].resume_token.clone();
+        state.suspend(bob);
+        let last = state.seq;
+        for _ in 0..3 {
+            state.on_listen(alice, &hello());
+        }
+        // as if the log was full
+        state.events.retain(|(seq, _)| *seq > last + 1);
+
+        let (socket, rx) = recorder();
+        let params = JoinParams {
+            resume: Some(resume),
+            last: Some(last),
+            ..join_params("bob")
+        };
+        let id = state.join(socket, params);
+        let replay = serde_json::json!({"textroom": "replay", "since": last, "transaction": "1"});
+        state.on_listen(id, &replay.to_string());
+        drop(state);
+
+        let received = received(rx).await;
+        assert_eq!(received[0]["resumed"], true);
+        assert_eq!(received[0]["gap"], true);
+        assert_eq!(seqs(&received), [last + 2, last + 3]);
+        let response = received.last().unwrap();
+        assert_eq!(response["transaction"], "1");
+        assert_eq!(response["code"], "events_unavailable");
+    }
+
+    #[tokio::test]
+    async fn test_expired_session() {
+        let mut state = state(None);
+        let (socket, alice_rx) = recorder();
+        state.join(socket, join_params("alice"));
+        let bob = state.join(sink(), join_params("bob"));
+        let resume = state.clients[&bob].resume_token.clone();
+        state.suspend(bob);
+        state.expire_sessions();
+        assert_eq!(state.count(), 2);
+        for session in state.suspended.values_mut() {
+            session.deadline = Instant::now();
+        }
+        state.expire_sessions();
+        assert_eq!(state.count(), 1);
+
+        // too late, a new session starts
+        let (socket, bob_rx) = recorder();
+        let params = JoinParams {
+            resume: Some(resume),
+            ..join_params("bob")
+        };
+        state.join(socket, params);
+        drop(state);
+        assert_eq!(received(bob_rx).await[0]["resumed"], false);
+        let presence = received_presence(alice_rx).await;
+        let presence: Vec<&serde_json::Value> = presence.iter().map(|e| &e["textroom"]).collect();
+        assert_eq!(presence, ["join", "leave", "join"]);
+    }
+
+    #[tokio::test]
+    async fn test_superseded_connection() {
+        let mut state = state(None);
+        let (socket, rx) = recorder();
+        let bob = state.join(socket, join_params("bob"));
+        let resume = state.clients[&bob].resume_token.clone();
+        let params = JoinParams {
+            resume: Some(resume),
+            ..join_params("bob")
+        };
+        let id = state.join(sink(), params);
+        assert!(!state.clients.contains_key(&bob));
+        assert!(state.clients.contains_key(&id));
+        assert_eq!(state.count(), 1);
+
+        let messages: Vec<WsMessage> = rx.collect().await;
+        let Some(WsMessage::Close(Some(frame))) = messages.last() else {
+            panic!("not closed: {messages:?}");
+        };
+        assert_eq!(u16::from(frame.code), SESSION_RESUMED_CLOSE_CODE);
+    }
+
+    #[tokio::test]
+    async fn test_resume_replay_max() {
+        let mut state = state(None);
+        let alice = state.join(sink(), join_params("alice"));
+        let bob = state.join(sink(), join_params("bob"));
+        let resume = state.clients[&bob].resume_token.clone();
+        let last = state.seq;
+        state.suspend(bob);
+        for _ in 0..CLIENT_QUEUE_SIZE + 100 {
+            state.on_listen(alice, &hello());
+        }
+
+        let (socket, rx) = recorder();
+        let params = JoinParams {
+            resume: Some(resume),
+            last: Some(last),
+            ..join_params("bob")
+        };
+        state.join(socket, params);
+        let seq = state.seq;
+        drop(state);
+        let received = received(rx).await;
+        assert_eq!(received[0]["textroom"], "welcome");
+        assert_eq!(received[0]["resumed"], true);
+        assert_eq!(received[0]["gap"], true);
+        assert_eq!(received.len(), REPLAY_MAX + 1);
+        assert_eq!(received[1]["seq"], seq - REPLAY_MAX as u64 + 1);
+        assert_eq!(received[REPLAY_MAX]["seq"], seq);
+    }
+
+    #[tokio::test]
+    async fn test_presence_sync() {
+        let backplane: Arc<dyn Backplane> = Arc::new(InProcessBackplane::default());
+        let mut published = backplane.subscribe(&room_channel("/app/room"));
+        let mut state = state(Some(Replication::new(backplane.clone())));
+        // the presence syncs published so far, without the room events
+        let mut syncs = || -> Vec<serde_json::Value> {
+            std::iter::from_fn(|| published.try_recv().ok())
+                .map(|e| serde_json::from_str::<serde_json::Value>(&e).unwrap())
+                .filter(|e| e["sync"] != "event")
+                .collect()
+        };
+
+        state.join(sink(), join_params("a"));
+        state.publish_presence();
+        let sync = &syncs()[0];
+        assert_eq!(sync["sync"], "presence");
+        assert_eq!(sync["participants"][0]["username"], "a");
+        let id = state.join(sink(), join_params("b"));
+        state.leave(id);
+        state.join(sink(), join_params("c"));
+        state.publish_presence();
+        let sync = &syncs()[0];
+        assert_eq!(sync["sync"], "presenceChanged");
+        assert_eq!(
+            sync["joined"],
+            serde_json::json!([{"username": "c", "display": "C"}])
+        );
+        assert_eq!(sync["left"], serde_json::json!([]));
+        state.publish_presence();
+        assert!(syncs().is_empty());
+
+        let instance = || "other".to_string();
+        state.on_sync(RoomSync::Presence {
+            instance: instance(),
+            participants: vec![participant("c", "C"), participant("d", "D")],
+        });
+        state.on_sync(RoomSync::PresenceChanged {
+            instance: instance(),
+            joined: vec![participant("e", "E")],
+            left: vec![participant("c", "C")],
+        });
+        assert_eq!(
+            state.remote["other"].participants,
+            [participant("d", "D"), participant("e", "E")]
+        );
+        state.on_sync(RoomSync::PresenceChanged {
+            instance: instance(),
+            joined: Vec::new(),
+            left: vec![participant("d", "D"), participant("e", "E")],
+        });
+        assert!(state.remote.is_empty());
+    }
+
+    #[test]
+    fn test_replica_expiry() {
+        let backplane: Arc<dyn Backplane> = Arc::new(InProcessBackplane::default());
+        let mut published = backplane.subscribe(&room_channel("/app/room"));
+        let mut origin = state(Some(Replication::new(backplane.clone())));
+        origin.publish_refresh();
+        origin.publish_refresh();
+        let sync: serde_json::Value = serde_json::from_str(&published.try_recv().unwrap()).unwrap();
+        assert_eq!(sync["sync"], "refresh");
+        assert!(published.try_recv().is_err());
+
+        let mut replica = state(Some(Replication::new(backplane)));
+        replica.is_replica = true;
+        replica.origin_deadline = Instant::now();
+        replica.on_sync(RoomSync::Refresh);
+        replica.expire_room();
+        assert!(!replica.is_destroyed);
+
+        // the instance of the room missed its refresh, and so did its participants
+        let participants = vec![participant("a", "A")];
+        let deadline = Instant::now();
+        replica.remote.insert(
+            "other".to_string(),
+            RemotePresence {
+                participants,
+                deadline,
+            },
+        );
+        replica.origin_deadline = Instant::now();
+        replica.publish_presence();
+        assert!(replica.remote.is_empty());
+        replica.expire_room();
+        assert!(replica.is_destroyed);
+    }
+
+    #[test]
+    fn test_participants_page() {
+        let mut state = state(None);
+        let participants = vec![
+            participant("carol", "Carol"),
+            participant("alice", "Alice"),
+            participant("bob", "Bobby"),
+            participant("al", "Big Al"),
+        ];
+        let deadline = Instant::now() + PRESENCE_TTL;
+        state.remote.insert(
+            "instance".to_string(),
+            RemotePresence {
+                participants,
+                deadline,
+            },
+        );
+
+        let (total, page) = state.participants_page(None, 1, Some(2));
+        assert_eq!(total, 4);
+        assert_eq!(
+            page,
+            [participant("al", "Big Al"), participant("bob", "Bobby")]
+        );
+        let (total, page) = state.participants_page(Some("AL"), 0, None);
+        assert_eq!(total, 2);
+        assert_eq!(
+            page,
+            [participant("alice", "Alice"), participant("al", "Big Al")]
+        );
+        assert_eq!(state.participants_page(Some(""), 0, None).0, 4);
+    }
+}
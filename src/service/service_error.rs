@@ -1,5 +1,76 @@
+use crate::misc::ActorGone;
+use crate::model::ErrorCode;
+
 #[derive(Debug)]
 pub enum ServiceError {
     RoomNotFound,
+    RoomAlreadyExists,
+    InvalidRoomName,
     SecretNotMatch,
+    Forbidden,
+    MessageNotFound,
+    PollNotFound,
+    InvalidArgument(&'static str),
+    LimitExceeded(&'static str),
+    /// A namespace of the room has as many rooms as it allows.
+    NamespaceFull,
+    RoomFull,
+    /// The room exists with other values of these settings.
+    SettingsConflict(Vec<&'static str>),
+    ActorGone,
+}
+
+impl ServiceError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ServiceError::RoomNotFound => ErrorCode::RoomNotFound,
+            ServiceError::RoomAlreadyExists => ErrorCode::RoomAlreadyExists,
+            ServiceError::InvalidRoomName => ErrorCode::InvalidRoomName,
+            ServiceError::SecretNotMatch => ErrorCode::SecretMismatch,
+            ServiceError::Forbidden => ErrorCode::Forbidden,
+            ServiceError::MessageNotFound => ErrorCode::MessageNotFound,
+            ServiceError::PollNotFound => ErrorCode::PollNotFound,
+            ServiceError::InvalidArgument(_) => ErrorCode::InvalidArgument,
+            ServiceError::LimitExceeded(_) => ErrorCode::LimitExceeded,
+            ServiceError::NamespaceFull => ErrorCode::NamespaceFull,
+            ServiceError::RoomFull => ErrorCode::RoomFull,
+            ServiceError::SettingsConflict(_) => ErrorCode::SettingsConflict,
+            ServiceError::ActorGone => ErrorCode::RoomUnavailable,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ServiceError::RoomNotFound => "Room not found".to_string(),
+            ServiceError::RoomAlreadyExists => "Room already exists".to_string(),
+            ServiceError::InvalidRoomName => "Room name is invalid".to_string(),
+            ServiceError::SecretNotMatch => "Secret does not match".to_string(),
+            ServiceError::Forbidden => "Not allowed".to_string(),
+            ServiceError::MessageNotFound => "Message not found".to_string(),
+            ServiceError::PollNotFound => "Poll not found".to_string(),
+            ServiceError::InvalidArgument(name) => format!("{name} is invalid."),
+            ServiceError::LimitExceeded(name) => format!("{name} is over the limit."),
+            ServiceError::NamespaceFull => "Namespace has too many rooms".to_string(),
+            ServiceError::RoomFull => "Room is full".to_string(),
+            ServiceError::SettingsConflict(fields) => {
+                format!("Room already exists with other {}.", fields.join(", "))
+            }
+            ServiceError::ActorGone => "Room is gone".to_string(),
+        }
+    }
+
+    /// Params the error is about.
+    pub fn fields(&self) -> Vec<&'static str> {
+        match self {
+            ServiceError::InvalidArgument(name) | ServiceError::LimitExceeded(name) => vec![name],
+            ServiceError::SettingsConflict(fields) => fields.clone(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl From<ActorGone> for ServiceError {
+    fn from(_: ActorGone) -> Self {
+        ServiceError::ActorGone
+    }
 }